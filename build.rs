@@ -0,0 +1,84 @@
+//! Generates the usage tables from the machine-readable HID Usage Tables in
+//! data/HidUsageTables.json, in the format published by usb.org.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+const TABLES_PATH: &str = "data/HidUsageTables.json";
+
+/// Returns an ID of the tables, which are usage pages and usage IDs, as a u16.
+fn as_u16(value: &Value) -> Option<u16> {
+    value.as_u64().and_then(|value| u16::try_from(value).ok())
+}
+
+/// Write the table of every usage page, sorted by ID, with the usages of each page sorted by ID.
+/// The kinds of each usage are not needed, and are ignored.
+fn generate(tables: &Value) -> Result<String, String> {
+    let mut pages: Vec<&Value> = tables["UsagePages"]
+        .as_array()
+        .ok_or("missing UsagePages")?
+        .iter()
+        .collect();
+    pages.sort_by_key(|page| as_u16(&page["Id"]));
+
+    let mut out = String::new();
+    writeln!(out, "// Generated by build.rs from {}. Do not edit.", TABLES_PATH).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "/// Every usage page of the HID Usage Tables, sorted by ID.").unwrap();
+    writeln!(out, "pub const PAGE_TABLES: &[PageTable] = &[").unwrap();
+    for page in pages {
+        let id = as_u16(&page["Id"]).ok_or("usage page without an ID")?;
+        let name = page["Name"].as_str().ok_or("usage page without a name")?;
+        let mut usages = page["UsageIds"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|usage| {
+                let usage_id = as_u16(&usage["Id"]);
+                let usage_name = usage["Name"].as_str();
+                usage_id.zip(usage_name).ok_or(format!("invalid usage on page {:#06X}", id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        usages.sort_by_key(|(usage_id, _)| *usage_id);
+
+        writeln!(out, "    PageTable {{").unwrap();
+        writeln!(out, "        id: {:#06X},", id).unwrap();
+        writeln!(out, "        name: {:?},", name).unwrap();
+        writeln!(out, "        usages: &[").unwrap();
+        for (usage_id, usage_name) in usages {
+            writeln!(out, "            ({:#06X}, {:?}),", usage_id, usage_name).unwrap();
+        }
+        writeln!(out, "        ],").unwrap();
+        match &page["UsageIdGenerator"] {
+            generator @ Value::Object(_) => {
+                let prefix = generator["NamePrefix"].as_str().ok_or("generator without a prefix")?;
+                let start = as_u16(&generator["StartUsageId"]).ok_or("generator without a start")?;
+                let end = as_u16(&generator["EndUsageId"]).ok_or("generator without an end")?;
+                writeln!(out, "        generator: Some(UsageGenerator {{ name_prefix: {:?}, start: {:#06X}, end: {:#06X} }}),", prefix, start, end).unwrap();
+            },
+            _ => writeln!(out, "        generator: None,").unwrap(),
+        }
+        writeln!(out, "    }},").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    Ok(out)
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", TABLES_PATH);
+
+    let text = fs::read_to_string(TABLES_PATH).expect("failed to read the usage tables");
+    let tables: Value = serde_json::from_str(&text)
+        .unwrap_or_else(|error| panic!("failed to parse {}: {}", TABLES_PATH, error));
+    let generated = generate(&tables)
+        .unwrap_or_else(|error| panic!("invalid usage tables in {}: {}", TABLES_PATH, error));
+
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("usage_tables.rs");
+    fs::write(out_path, generated).expect("failed to write the usage tables");
+}
@@ -1,6 +1,7 @@
 // Collections
 // Need to get report items, build reports. and ensure alignment.
 
+use super::item::LongItem;
 use super::report::Report;
 use super::usage::Usage;
 use super::field_types::{CollectionType, Delimiter, DesignatorIndex, StringIndex};
@@ -9,6 +10,8 @@ use super::field_types::{CollectionType, Delimiter, DesignatorIndex, StringIndex
 pub enum CollectionItem {
     Report(Report),
     Collection(Collection),
+    /// A vendor long item, kept in place so that it is emitted again when compiling.
+    LongItem(LongItem),
 }
 
 impl From<Report> for CollectionItem {
@@ -23,6 +26,12 @@ impl From<Collection> for CollectionItem {
     }
 }
 
+impl From<LongItem> for CollectionItem {
+    fn from(long_item: LongItem) -> Self {
+        Self::LongItem(long_item)
+    }
+}
+
 /// Helper struct to convert various containers into a collection of items
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CollectionItems(Box<[CollectionItem]>);
@@ -110,7 +119,7 @@ impl Collection {
         }
     }
 
-    pub fn items<'a>(&'a self) -> &'a [CollectionItem] {
+    pub fn items(&self) -> &[CollectionItem] {
         &self.items
     }
 
@@ -0,0 +1,434 @@
+//! Reconstruction of Collections from a linear tag document.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::iter::FromIterator;
+use std::mem::{discriminant, take};
+
+use super::collection::{Collection, CollectionItem};
+use super::descriptor::ReportDescriptor;
+use super::error::DecodeError;
+use super::field_types::{CollectionType, DesignatorIndex, StringIndex};
+use super::item::{Items, LongItem};
+use super::optimizer::GlobalTable;
+use super::report::{Report, ReportMain};
+use super::tag::Tag;
+use super::usage::{Usage, UsageRange, UsageSet};
+
+/// Reason a tag document could not be decompiled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DecompileErrorKind {
+    /// A Pop tag was found with no matching Push.
+    UnmatchedPop,
+    /// An End Collection tag was found with no open collection.
+    UnmatchedEndCollection,
+    /// The document ended while a collection was still open.
+    UnclosedCollection,
+    /// A main item or a long item was found outside of any collection.
+    OutsideCollection,
+    /// A main item required a global item, given by name, that was never set.
+    MissingGlobal(&'static str),
+    /// A Usage Minimum was not matched with a Usage Maximum, or the reverse.
+    UnpairedUsageRange,
+    /// A Usage Minimum and Usage Maximum do not form a valid range.
+    InvalidUsageRange,
+}
+
+impl Display for DecompileErrorKind {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnmatchedPop => "pop without push".fmt(fmt),
+            Self::UnmatchedEndCollection => "end collection without collection".fmt(fmt),
+            Self::UnclosedCollection => "collection is never closed".fmt(fmt),
+            Self::OutsideCollection => "item outside of a collection".fmt(fmt),
+            Self::MissingGlobal(name) => write!(fmt, "missing global item {}", name),
+            Self::UnpairedUsageRange => "usage minimum and maximum should be paired".fmt(fmt),
+            Self::InvalidUsageRange => "invalid usage range".fmt(fmt),
+        }
+    }
+}
+
+/// Error type when a tag document cannot be rebuilt into Collections.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DecompileError {
+    /// Index of the tag at which decompilation failed.
+    pub index: usize,
+    /// Reason decompilation failed.
+    pub kind: DecompileErrorKind,
+}
+
+impl DecompileError {
+    /// Construct a DecompileError for the tag at the given index.
+    pub const fn new(index: usize, kind: DecompileErrorKind) -> Self {
+        Self { index, kind }
+    }
+}
+
+impl Display for DecompileError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} at tag {}", self.kind, self.index)
+    }
+}
+impl Error for DecompileError {}
+
+
+/// Local state table. Cleared after every main item.
+#[derive(Clone, Debug, Default)]
+struct LocalTable {
+    usage_set: UsageSet,
+    usage_minimum: Option<Usage>,
+    usage_maximum: Option<Usage>,
+    designator_index: Option<DesignatorIndex>,
+    designator_minimum: Option<DesignatorIndex>,
+    designator_maximum: Option<DesignatorIndex>,
+    string_index: Option<StringIndex>,
+    string_minimum: Option<StringIndex>,
+    string_maximum: Option<StringIndex>,
+    /// Number of usages declared in the currently open delimited set, if a set is open.
+    delimited_usages: Option<usize>,
+}
+
+impl LocalTable {
+    /// Returns false if the usage is an alternative usage inside of a delimited set. Only the first
+    /// usage of a delimited set is kept.
+    fn accepts_usage(&mut self) -> bool {
+        match &mut self.delimited_usages {
+            Some(count) => {
+                *count += 1;
+                *count == 1
+            },
+            None => true,
+        }
+    }
+
+    /// Add a single usage.
+    fn push_usage(&mut self, usage: Usage) {
+        if self.accepts_usage() {
+            self.usage_set.push_usage(usage);
+        }
+    }
+
+    /// Add a usage range once both of its bounds are known.
+    fn complete_usage_range(&mut self) -> Result<(), DecompileErrorKind> {
+        if let (Some(min), Some(max)) = (self.usage_minimum, self.usage_maximum) {
+            self.usage_minimum = None;
+            self.usage_maximum = None;
+            let is_valid = min <= max
+                && discriminant(&min) == discriminant(&max)
+                && min.page() == max.page();
+            if !is_valid {
+                return Err(DecompileErrorKind::InvalidUsageRange);
+            }
+            if self.accepts_usage() {
+                self.usage_set.push_usage_range(UsageRange::new(min, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Consume this table, ensuring that all usage ranges were completed.
+    fn finish(self) -> Result<Self, DecompileErrorKind> {
+        if self.usage_minimum.is_some() || self.usage_maximum.is_some() {
+            Err(DecompileErrorKind::UnpairedUsageRange)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// A collection which has been opened, but not yet closed.
+struct OpenCollection {
+    collection: Collection,
+    items: Vec<CollectionItem>,
+}
+
+/// State of the item parser at a given point in the tag document.
+#[derive(Default)]
+struct ParserState {
+    global_table: GlobalTable,
+    global_stack: Vec<GlobalTable>,
+    local_table: LocalTable,
+    open_collections: Vec<OpenCollection>,
+    collections: Vec<Collection>,
+}
+
+impl ParserState {
+    /// Returns the current usage page, or the undefined page if it was never set.
+    fn usage_page(&self) -> u16 {
+        self.global_table.usage_page.unwrap_or(0)
+    }
+
+    /// Add a report for an Input, Output, or Feature item.
+    fn push_report(&mut self, main: ReportMain) -> Result<(), DecompileErrorKind> {
+        let local_table = take(&mut self.local_table).finish()?;
+        let report = make_report(main, &self.global_table, local_table)?;
+        let parent = self.open_collections.last_mut()
+            .ok_or(DecompileErrorKind::OutsideCollection)?;
+        parent.items.push(report.into());
+        Ok(())
+    }
+
+    /// Open a collection for a Collection item.
+    fn open_collection(&mut self, collection_type: CollectionType) -> Result<(), DecompileErrorKind> {
+        let usage_page = self.usage_page();
+        let local_table = take(&mut self.local_table).finish()?;
+        self.open_collections.push(OpenCollection {
+            collection: make_collection(collection_type, usage_page, local_table),
+            items: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Close the innermost collection for an End Collection item.
+    fn close_collection(&mut self) -> Result<(), DecompileErrorKind> {
+        self.local_table = LocalTable::default();
+        let OpenCollection { mut collection, items } = self.open_collections.pop()
+            .ok_or(DecompileErrorKind::UnmatchedEndCollection)?;
+        collection.items = items.into_boxed_slice();
+        match self.open_collections.last_mut() {
+            Some(parent) => parent.items.push(collection.into()),
+            None => self.collections.push(collection),
+        }
+        Ok(())
+    }
+
+    /// Keep a long item in place within the innermost collection.
+    fn push_long_item(&mut self, long_item: LongItem) -> Result<(), DecompileErrorKind> {
+        let parent = self.open_collections.last_mut()
+            .ok_or(DecompileErrorKind::OutsideCollection)?;
+        parent.items.push(long_item.into());
+        Ok(())
+    }
+
+    /// Incorporate a single tag into the state.
+    fn apply(&mut self, tag: Tag) -> Result<(), DecompileErrorKind> {
+        let usage_page = self.usage_page();
+        let local_table = &mut self.local_table;
+        match tag {
+            // Main items
+            Tag::Input(flags) => self.push_report(ReportMain::new_input(flags))?,
+            Tag::Output(flags) => self.push_report(ReportMain::new_output(flags))?,
+            Tag::Feature(flags) => self.push_report(ReportMain::new_feature(flags))?,
+            Tag::Collection(collection_type) => self.open_collection(collection_type)?,
+            Tag::EndCollection => self.close_collection()?,
+
+            // Global items
+            Tag::Push => self.global_stack.push(self.global_table),
+            Tag::Pop => {
+                self.global_table = self.global_stack.pop()
+                    .ok_or(DecompileErrorKind::UnmatchedPop)?;
+            },
+            Tag::UsagePage(..)
+                | Tag::LogicalMinimum(..)
+                | Tag::LogicalMaximum(..)
+                | Tag::PhysicalMinimum(..)
+                | Tag::PhysicalMaximum(..)
+                | Tag::UnitExponent(..)
+                | Tag::Unit(..)
+                | Tag::ReportSize(..)
+                | Tag::ReportId(..)
+                | Tag::ReportCount(..) => {
+                self.global_table.set_tag(tag);
+            },
+
+            // Local items
+            Tag::ExtendedUsage(usage) => local_table.push_usage(Usage::from_extended(usage)),
+            Tag::UsageId(id) => local_table.push_usage(Usage::new(usage_page, id)),
+            Tag::ExtendedUsageMinimum(usage) => {
+                local_table.usage_minimum = Some(Usage::from_extended(usage));
+                local_table.complete_usage_range()?;
+            },
+            Tag::UsageMinimumId(id) => {
+                local_table.usage_minimum = Some(Usage::new(usage_page, id));
+                local_table.complete_usage_range()?;
+            },
+            Tag::ExtendedUsageMaximum(usage) => {
+                local_table.usage_maximum = Some(Usage::from_extended(usage));
+                local_table.complete_usage_range()?;
+            },
+            Tag::UsageMaximumId(id) => {
+                local_table.usage_maximum = Some(Usage::new(usage_page, id));
+                local_table.complete_usage_range()?;
+            },
+            Tag::DesignatorIndex(v) => local_table.designator_index = Some(v),
+            Tag::DesignatorMinimum(v) => local_table.designator_minimum = Some(v),
+            Tag::DesignatorMaximum(v) => local_table.designator_maximum = Some(v),
+            Tag::StringIndex(v) => local_table.string_index = Some(v),
+            Tag::StringMinimum(v) => local_table.string_minimum = Some(v),
+            Tag::StringMaximum(v) => local_table.string_maximum = Some(v),
+            Tag::Delimiter(delimiter) => {
+                local_table.delimited_usages = if delimiter.is_open() { Some(0) } else { None };
+            },
+
+            // Long items
+            Tag::LongItem(long_item) => self.push_long_item(long_item)?,
+        }
+        Ok(())
+    }
+}
+
+
+/// A Decompiler rebuilds Collections from a linear sequence of tags by following the global and
+/// local state of each item, as described in 6.2.2 of the USB HID specification.
+///
+/// Long items are kept as items of their collection, ahead of the report which follows them.
+/// Alternative usages inside of delimited sets are skipped, keeping only the first usage of each
+/// set.
+#[derive(Clone, Debug, Default)]
+pub struct Decompiler(Vec<Tag>);
+
+impl FromIterator<Tag> for Decompiler {
+    /// Create a Decompiler from an iterable of tags.
+    fn from_iter<T: IntoIterator<Item = Tag>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Decompiler {
+    /// Create a Decompiler from the bytes of a report descriptor.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let tags = Items::from_bytes(bytes)?.into_tags()?;
+        Ok(Self(tags.into_vec()))
+    }
+
+    /// Rebuild the top-level collections described by the tags.
+    pub fn collections(self) -> Result<Box<[Collection]>, DecompileError> {
+        let tag_count = self.0.len();
+        let mut state = ParserState::default();
+
+        for (index, tag) in self.0.into_iter().enumerate() {
+            state.apply(tag).map_err(|kind| DecompileError::new(index, kind))?;
+        }
+
+        if !state.open_collections.is_empty() {
+            let kind = DecompileErrorKind::UnclosedCollection;
+            return Err(DecompileError::new(tag_count, kind));
+        }
+
+        Ok(state.collections.into_boxed_slice())
+    }
+
+    /// Rebuild the report descriptor described by the tags.
+    pub fn descriptor(self) -> Result<ReportDescriptor, DecompileError> {
+        self.collections().map(ReportDescriptor::from)
+    }
+}
+
+/// Build a Collection from the local state preceding its Collection tag.
+/// A collection without a usage is given the undefined usage.
+fn make_collection(
+    collection_type: CollectionType,
+    usage_page: u16,
+    local_table: LocalTable
+) -> Collection {
+    let usage = local_table.usage_set.into_iter()
+        .next()
+        .map(|range| range.min)
+        .unwrap_or(Usage::new(usage_page, 0));
+    let mut collection = Collection::empty(collection_type, usage);
+    collection.designator_index = local_table.designator_index;
+    collection.string_index = local_table.string_index;
+    collection
+}
+
+/// Build a Report from the global and local state preceding its main item.
+fn make_report(
+    main: ReportMain,
+    global_table: &GlobalTable,
+    local_table: LocalTable
+) -> Result<Report, DecompileErrorKind> {
+    let missing = DecompileErrorKind::MissingGlobal;
+    let mut report = Report::new(
+        main,
+        local_table.usage_set,
+        global_table.logical_minimum.ok_or(missing("Logical Minimum"))?,
+        global_table.logical_maximum.ok_or(missing("Logical Maximum"))?,
+        global_table.report_size.ok_or(missing("Report Size"))?,
+        global_table.report_count.ok_or(missing("Report Count"))?);
+
+    report.physical_minimum = global_table.physical_minimum;
+    report.physical_maximum = global_table.physical_maximum;
+    report.unit_exponent = global_table.unit_exponent;
+    report.unit = global_table.unit;
+    report.report_id = global_table.report_id;
+
+    report.designator_index = local_table.designator_index;
+    report.designator_minimum = local_table.designator_minimum;
+    report.designator_maximum = local_table.designator_maximum;
+    report.string_index = local_table.string_index;
+    report.string_minimum = local_table.string_minimum;
+    report.string_maximum = local_table.string_maximum;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mouse with three buttons, padding, and a vendor long item between the buttons and X/Y.
+    const MOUSE: [u8; 52] = [
+        0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x09, 0x01, 0xA1, 0x00,
+        0x05, 0x09, 0x19, 0x01, 0x29, 0x03, 0x15, 0x00, 0x25, 0x01,
+        0x95, 0x03, 0x75, 0x01, 0x81, 0x02, 0x95, 0x01, 0x75, 0x05,
+        0x81, 0x01, 0xFE, 0x01, 0x42, 0x99, 0x05, 0x01, 0x09, 0x30,
+        0x09, 0x31, 0x15, 0x81, 0x25, 0x7F, 0x75, 0x08, 0x95, 0x02,
+        0x81, 0x06,
+    ];
+
+    fn mouse_bytes() -> Vec<u8> {
+        let mut bytes = MOUSE.to_vec();
+        bytes.extend([0xC0, 0xC0]);
+        bytes
+    }
+
+    fn usage_count(usage_set: &UsageSet) -> u32 {
+        usage_set.clone().into_iter().map(|usage_range| usage_range.len()).sum()
+    }
+
+    #[test]
+    fn rebuilds_nested_collections() {
+        let collections = Decompiler::from_bytes(&mouse_bytes()).unwrap().collections().unwrap();
+        assert_eq!(collections.len(), 1);
+        let application = &collections[0];
+        assert_eq!(application.collection_type, CollectionType::Application);
+        assert_eq!(application.usage, Usage::new(0x01, 0x02));
+        let physical = match application.items() {
+            [CollectionItem::Collection(physical)] => physical,
+            items => panic!("unexpected items {:?}", items),
+        };
+        assert_eq!(physical.usage, Usage::new(0x01, 0x01));
+        match physical.items() {
+            [CollectionItem::Report(buttons), CollectionItem::Report(padding), CollectionItem::LongItem(long_item), CollectionItem::Report(axes)] => {
+                assert_eq!(buttons.report_count, 3);
+                assert_eq!(usage_count(&buttons.usage_set), 3);
+                assert_eq!(padding.report_size, 5);
+                assert_eq!((long_item.tag(), long_item.data()), (0x42, &[0x99][..]));
+                assert_eq!(axes.logical_minimum, -127);
+                assert_eq!(usage_count(&axes.usage_set), 2);
+            },
+            items => panic!("unexpected items {:?}", items),
+        }
+    }
+
+    #[test]
+    fn keeps_long_items_when_compiling() {
+        let collections = Decompiler::from_bytes(&mouse_bytes()).unwrap().collections().unwrap();
+        let bytes = collections[0].clone().into_bytes();
+        assert!(bytes.windows(4).any(|window| window == [0xFE, 0x01, 0x42, 0x99]));
+        assert_eq!(Decompiler::from_bytes(&bytes).unwrap().collections().unwrap(), collections);
+    }
+
+    #[test]
+    fn reports_the_failing_tag() {
+        let error = |bytes: &[u8]| Decompiler::from_bytes(bytes).unwrap().collections().unwrap_err();
+        assert_eq!(error(&MOUSE), DecompileError::new(25, DecompileErrorKind::UnclosedCollection));
+        assert_eq!(error(&[0xB4]), DecompileError::new(0, DecompileErrorKind::UnmatchedPop));
+        assert_eq!(error(&[0xC0]), DecompileError::new(0, DecompileErrorKind::UnmatchedEndCollection));
+        assert_eq!(error(&[0xFE, 0x00, 0x42]), DecompileError::new(0, DecompileErrorKind::OutsideCollection));
+        assert_eq!(error(&[0xA1, 0x01, 0x19, 0x01, 0x81, 0x02]),
+            DecompileError::new(2, DecompileErrorKind::UnpairedUsageRange));
+        assert_eq!(error(&[0xA1, 0x01, 0x75, 0x08, 0x81, 0x02]),
+            DecompileError::new(2, DecompileErrorKind::MissingGlobal("Logical Minimum")));
+    }
+}
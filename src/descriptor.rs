@@ -0,0 +1,101 @@
+//! Report descriptors with several top-level collections.
+
+use super::collection::Collection;
+
+/// A report descriptor, made of an ordered list of top-level collections.
+///
+/// Composite devices, such as a keyboard with consumer controls, describe each function in its
+/// own top-level Application collection. The collections are compiled into a single descriptor,
+/// and share report IDs and global items.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReportDescriptor {
+    pub collections: Box<[Collection]>,
+}
+
+impl ReportDescriptor {
+    /// Construct a descriptor without any collections.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Construct a descriptor from top-level collections, in order.
+    pub fn new<I: IntoIterator<Item = Collection>>(collections: I) -> Self {
+        collections.into_iter().collect()
+    }
+
+    pub fn collections(&self) -> &[Collection] {
+        &self.collections
+    }
+
+    pub fn into_collections(self) -> Box<[Collection]> {
+        self.collections
+    }
+
+    /// Add a top-level collection after the existing collections, returning this descriptor.
+    pub fn with_collection(self, collection: Collection) -> Self {
+        self.collections.into_vec().into_iter().chain([collection]).collect()
+    }
+}
+
+impl FromIterator<Collection> for ReportDescriptor {
+    fn from_iter<I: IntoIterator<Item = Collection>>(iter: I) -> Self {
+        Self {
+            collections: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl From<Collection> for ReportDescriptor {
+    fn from(collection: Collection) -> Self {
+        Self::new([collection])
+    }
+}
+
+impl From<Box<[Collection]>> for ReportDescriptor {
+    fn from(collections: Box<[Collection]>) -> Self {
+        Self { collections }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::decompiler::Decompiler;
+    use super::super::field_types::{CollectionType, ReportFlags};
+    use super::super::item::Items;
+    use super::super::report::Report;
+    use super::super::tag::Tag;
+    use super::super::usage::{Usage, UsageSet};
+    use super::super::usage_tables::{generic_desktop, keyboard_keypad, page};
+
+    fn application(usage: Usage, key: Usage, report_id: u8) -> Collection {
+        let usage_set = UsageSet::empty().with_usage(key);
+        let report = Report::new_input(ReportFlags::new().as_variable(), usage_set, 0, 1, 8, 1);
+        Collection::new(CollectionType::Application, usage, (report.with_report_id(report_id),))
+    }
+
+    fn keyboard_with_consumer_controls() -> ReportDescriptor {
+        ReportDescriptor::new([
+            application(generic_desktop::KEYBOARD, keyboard_keypad::new(0x04), 1),
+            application(Usage::new(page::CONSUMER, 0x01), Usage::new(page::CONSUMER, 0xE9), 2),
+        ])
+    }
+
+    #[test]
+    fn shares_global_items_across_collections() {
+        let bytes = keyboard_with_consumer_controls().into_bytes();
+        let tags = Items::from_bytes(&bytes).unwrap().into_tags().unwrap();
+        let count = |matches: fn(&Tag) -> bool| tags.iter().filter(|tag| matches(tag)).count();
+        assert_eq!(count(|tag| matches!(tag, Tag::Collection(_))), 2);
+        assert_eq!(count(|tag| matches!(tag, Tag::ReportSize(_))), 1);
+        assert_eq!(count(|tag| matches!(tag, Tag::ReportId(_))), 2);
+    }
+
+    #[test]
+    fn decompiles_every_top_level_collection() {
+        let descriptor = keyboard_with_consumer_controls();
+        let bytes = descriptor.clone().into_bytes();
+        let decompiled = Decompiler::from_bytes(&bytes).unwrap().descriptor().unwrap();
+        assert_eq!(decompiled, descriptor);
+    }
+}
@@ -1,15 +1,33 @@
 use std::fmt::{self, Display, Formatter};
 use std::error::Error;
 
-/// Error type when a report descriptor has some fields with ID and others without.
+use super::field_types::ReportId;
+use super::report::ReportType;
+
+/// Error type when the Report IDs of a report descriptor break the rules of 6.2.2.7 of the USB HID
+/// specification.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub struct MissingIdError {}
-impl Display for MissingIdError {
+pub enum ReportIdError {
+    /// Reports of the given type have no ID, while the given ID is used. Either every report has
+    /// an ID, or no report has one.
+    MissingId {
+        report_type: ReportType,
+        report_id: ReportId,
+    },
+    /// The ID is reserved. Report ID 0 should not be used.
+    OutOfRange(ReportId),
+}
+
+impl Display for ReportIdError {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
-        "reports should have IDs if one has an ID".fmt(fmt)
+        match self {
+            Self::MissingId { report_type, report_id } =>
+                write!(fmt, "{:?} reports have no ID while report ID {} is used", report_type, report_id),
+            Self::OutOfRange(report_id) => write!(fmt, "report ID {} is out of range", report_id),
+        }
     }
 }
-impl Error for MissingIdError {}
+impl Error for ReportIdError {}
 
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -22,3 +40,82 @@ impl Display for TryFromIntError {
 }
 impl Error for TryFromIntError {}
 
+
+
+/// Error type when a unit exponent cannot be represented.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum UnitExponentError {
+    /// The exponent is outside of the range -8 to 7.
+    OutOfRange(i32),
+    /// The item data is neither a 4-bit nor a signed exponent.
+    InvalidData(i32),
+}
+
+impl Display for UnitExponentError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange(value) =>
+                write!(fmt, "unit exponent {} is outside of the range -8 to 7", value),
+            Self::InvalidData(data) =>
+                write!(fmt, "unit exponent data {:#x} is neither a nibble nor a signed byte", data),
+        }
+    }
+}
+impl Error for UnitExponentError {}
+
+impl From<UnitExponentError> for TryFromIntError {
+    /// Keeps conversions which returned a TryFromIntError before UnitExponentError was added.
+    fn from(_: UnitExponentError) -> Self {
+        Self {}
+    }
+}
+
+
+/// Error type when a report descriptor item cannot be interpreted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ItemError {
+    /// The descriptor ended before the end of the item.
+    Truncated,
+    /// The item prefix, given here, does not correspond to a known tag.
+    UnknownTag(u8),
+    /// The data of the item, with the given prefix, is not valid for its tag.
+    InvalidData(u8),
+}
+
+impl Display for ItemError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => "item truncated".fmt(fmt),
+            Self::UnknownTag(prefix) => write!(fmt, "unknown item prefix {:#04x}", prefix),
+            Self::InvalidData(prefix) => write!(fmt, "invalid data for item prefix {:#04x}", prefix),
+        }
+    }
+}
+impl Error for ItemError {}
+
+/// Error type when a report descriptor cannot be decoded from bytes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DecodeError {
+    /// Byte offset of the item that could not be decoded.
+    pub offset: usize,
+    /// Reason the item could not be decoded.
+    pub error: ItemError,
+}
+
+impl DecodeError {
+    /// Construct a DecodeError for the item at the given offset.
+    pub const fn new(offset: usize, error: ItemError) -> Self {
+        Self { offset, error }
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} at byte {}", self.error, self.offset)
+    }
+}
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
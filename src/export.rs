@@ -0,0 +1,138 @@
+//! Export of compiled report descriptors as annotated source code and hex dumps, with one item on
+//! each line.
+
+use std::fmt::Write;
+
+use super::item::{Item, Items, ShortItems};
+use super::listing::TagDescriber;
+use super::tag::Tag;
+
+/// An item of a descriptor, with its byte offset and the description of its tag.
+struct AnnotatedItem {
+    offset: usize,
+    bytes: Box<[u8]>,
+    description: String,
+}
+
+/// Describe each item in order. Items which are not a known tag are described by their prefix.
+fn annotate<'a, I: IntoIterator<Item = &'a Item>>(items: I) -> Vec<AnnotatedItem> {
+    let mut describer = TagDescriber::new();
+    let mut offset = 0;
+    items.into_iter()
+        .map(|item| {
+            let bytes = item.clone().into_bytes();
+            let description = match Tag::try_from(item.clone()) {
+                Ok(tag) => describer.describe(&tag).to_string(),
+                Err(_) => format!("Unknown Item (0x{:02X})", bytes[0]),
+            };
+            let annotated = AnnotatedItem { offset, bytes, description };
+            offset += annotated.bytes.len();
+            annotated
+        })
+        .collect()
+}
+
+/// Write the bytes of each item as a line of source code, followed by a comment. Comments are
+/// aligned after the longest item.
+fn write_source_lines(out: &mut String, items: &[AnnotatedItem]) {
+    let lines: Vec<String> = items.iter()
+        .map(|item| item.bytes.iter().map(|byte| format!("0x{:02X}, ", byte)).collect())
+        .collect();
+    let width = lines.iter().map(String::len).max().unwrap_or(0);
+    for (line, item) in lines.iter().zip(items) {
+        writeln!(out, "    {:width$}// {}", line, item.description, width = width).unwrap();
+    }
+}
+
+impl Items {
+    /// Export the items as a C array of bytes with the given name, with a comment describing each
+    /// item, such as `0x05, 0x01, // Usage Page (Generic Desktop)`.
+    pub fn to_c_array(&self, name: &str) -> String {
+        let items = annotate(self.iter());
+        let mut out = format!("static const uint8_t {}[] = {{\n", name);
+        write_source_lines(&mut out, &items);
+        out.push_str("};\n");
+        out
+    }
+
+    /// Export the items as a Rust constant of type `[u8; N]` with the given name, with a comment
+    /// describing each item.
+    pub fn to_rust_const(&self, name: &str) -> String {
+        let items = annotate(self.iter());
+        let len: usize = items.iter().map(|item| item.bytes.len()).sum();
+        let mut out = format!("const {}: [u8; {}] = [\n", name, len);
+        write_source_lines(&mut out, &items);
+        out.push_str("];\n");
+        out
+    }
+
+    /// Export the items as a hex dump, with the byte offset and description of each item, such as
+    /// `0000: 05 01  Usage Page (Generic Desktop)`.
+    pub fn to_hex_dump(&self) -> String {
+        let items = annotate(self.iter());
+        let lines: Vec<String> = items.iter()
+            .map(|item| item.bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" "))
+            .collect();
+        let width = lines.iter().map(String::len).max().unwrap_or(0);
+        let mut out = String::new();
+        for (line, item) in lines.iter().zip(&items) {
+            writeln!(out, "{:04X}: {:width$}  {}", item.offset, line, item.description, width = width).unwrap();
+        }
+        out
+    }
+}
+
+impl ShortItems {
+    /// Export the items as a C array of bytes, as by Items::to_c_array.
+    pub fn to_c_array(&self, name: &str) -> String {
+        self.to_items().to_c_array(name)
+    }
+
+    /// Export the items as a Rust constant of type `[u8; N]`, as by Items::to_rust_const.
+    pub fn to_rust_const(&self, name: &str) -> String {
+        self.to_items().to_rust_const(name)
+    }
+
+    /// Export the items as a hex dump, as by Items::to_hex_dump.
+    pub fn to_hex_dump(&self) -> String {
+        self.to_items().to_hex_dump()
+    }
+
+    fn to_items(&self) -> Items {
+        self.iter().copied().map(Item::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Items {
+        Items::from_bytes(&[0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x09, 0x01, 0xC0]).unwrap()
+    }
+
+    #[test]
+    fn writes_annotated_arrays() {
+        assert_eq!(items().to_c_array("mouse"), "\
+static const uint8_t mouse[] = {
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x02, // Usage (Mouse)
+    0xA1, 0x01, // Collection (Application)
+    0x09, 0x01, //     Usage (Pointer)
+    0xC0,       // End Collection
+};
+");
+        assert!(items().to_rust_const("MOUSE").starts_with("const MOUSE: [u8; 9] = [\n    0x05, 0x01, // Usage Page"));
+    }
+
+    #[test]
+    fn writes_hex_dumps_with_offsets() {
+        assert_eq!(items().to_hex_dump(), "\
+0000: 05 01  Usage Page (Generic Desktop)
+0002: 09 02  Usage (Mouse)
+0004: A1 01  Collection (Application)
+0006: 09 01      Usage (Pointer)
+0008: C0     End Collection
+");
+    }
+}
@@ -1,6 +1,9 @@
-/// Types for each field in a report descriptor.
+//! Types for each field in a report descriptor.
 
-use super::error::TryFromIntError;
+use std::fmt::{self, Display, Formatter};
+
+use super::error::{TryFromIntError, UnitExponentError};
+pub use super::unit::{Unit, UnitSystem};
 
 // Main item types
 
@@ -60,6 +63,9 @@ impl_report_flags!(
     (8, is_buffered_bytes, is_bit_field, as_buffered_bytes, as_bit_field),
 );
 
+/// Mask of the flags defined by the USB HID specification. Higher bits are reserved.
+const DEFINED_FLAGS: u32 = 0x1FF;
+
 impl From<u32> for ReportFlags {
     /// Construct ReportFlags from a u32.
     fn from(value: u32) -> Self {
@@ -74,6 +80,33 @@ impl From<ReportFlags> for u32 {
     }
 }
 
+impl Display for ReportFlags {
+    /// Format the flags as in the HID Descriptor Tool, such as "Data,Var,Abs". The first three
+    /// flags are always written, and the others only when set. Reserved bits which are set follow
+    /// as a hexadecimal value, such as "Data,Var,Abs,0x200".
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        fmt.write_str(if self.is_constant() { "Const" } else { "Data" })?;
+        fmt.write_str(if self.is_variable() { ",Var" } else { ",Array" })?;
+        fmt.write_str(if self.is_relative() { ",Rel" } else { ",Abs" })?;
+        let optional_flags = [
+            (self.can_wrap(), "Wrap"),
+            (self.is_nonlinear(), "Nonlinear"),
+            (self.has_no_preferred_state(), "No Preferred State"),
+            (self.has_null_state(), "Null State"),
+            (self.is_volatile(), "Volatile"),
+            (self.is_buffered_bytes(), "Buffered Bytes"),
+        ];
+        for (_, name) in optional_flags.iter().filter(|(is_set, _)| *is_set) {
+            write!(fmt, ",{}", name)?;
+        }
+        let reserved = self.0 & !DEFINED_FLAGS;
+        if reserved != 0 {
+            write!(fmt, ",0x{:X}", reserved)?;
+        }
+        Ok(())
+    }
+}
+
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum CollectionType {
@@ -101,6 +134,20 @@ impl CollectionType {
     }
 }
 
+impl Display for CollectionType {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Physical => "Physical",
+            Self::Application => "Application",
+            Self::Logical => "Logical",
+            Self::Report => "Report",
+            Self::NamedArray => "Named Array",
+            Self::UsageSwitch => "Usage Switch",
+            Self::UsageModifier => "Usage Modifier",
+        }.fmt(fmt)
+    }
+}
+
 impl TryFrom<u8> for CollectionType {
     type Error = TryFromIntError;
     /// Attempt to construct a CollectionType for the given u8.
@@ -137,45 +184,109 @@ pub type LogicalValue = i32;
 /// Contains units, which should be specified separately in Unit and UnitExponent tags.
 pub type PhysicalValue = i32;
 
-/// Value of the unit exponent in base 10.
-/// 4-bit 2's-complement value. Needs interpretation.
+/// How a UnitExponent is written in a report descriptor.
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-pub struct UnitExponent(i8);
+pub enum UnitExponentEncoding {
+    /// A 4-bit 2's-complement value, as described by the USB HID specification.
+    #[default]
+    Nibble,
+    /// A full 8-bit 2's-complement value, as written by many devices.
+    SignedByte,
+}
+
+/// Value of the unit exponent in base 10, from -8 to 7.
+///
+/// Exponents compare equal when they have the same value and are written the same way. As
+/// non-negative exponents are written the same way in both encodings, only the encoding of
+/// negative exponents is significant.
+#[derive(Copy, Clone, Debug, Default, Eq)]
+pub struct UnitExponent {
+    value: i8,
+    encoding: UnitExponentEncoding,
+}
 
 impl UnitExponent {
     /// Return the value of this exponent as a signed integer.
     pub fn as_i8(&self) -> i8 {
-        self.0
+        self.value
     }
     /// Return this exponent in its 4-bit form.
     pub fn as_nibble(&self) -> u8 {
-        (self.0 & 0xF).try_into().unwrap()
+        (self.value & 0xF) as u8
+    }
+
+    /// Return this exponent as a full signed byte.
+    pub fn as_signed_byte(&self) -> u8 {
+        self.value as u8
+    }
+
+    /// Return the encoding used when writing this exponent.
+    pub fn encoding(&self) -> UnitExponentEncoding {
+        self.encoding
+    }
+
+    /// Return this exponent, written with the given encoding.
+    pub fn with_encoding(self, encoding: UnitExponentEncoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Return this exponent as written, in the encoding of this exponent.
+    pub fn encoded(&self) -> u8 {
+        match self.encoding {
+            UnitExponentEncoding::Nibble => self.as_nibble(),
+            UnitExponentEncoding::SignedByte => self.as_signed_byte(),
+        }
+    }
+
+    /// Construct an exponent from its 4-bit form.
+    pub fn try_from_nibble(nibble: u8) -> Result<Self, UnitExponentError> {
+        if nibble & 0xF != nibble {
+            return Err(UnitExponentError::InvalidData(nibble.into()));
+        }
+        // Sign-extend the nibble.
+        Self::try_from(((nibble << 4) as i8) >> 4)
+    }
+
+    /// Construct an exponent from the sign-extended data of a Unit Exponent item, accepting both
+    /// the 4-bit form and the full signed form. The encoding is kept, so that the exponent is
+    /// written back the same way.
+    pub fn try_from_data(data: i32) -> Result<Self, UnitExponentError> {
+        match data {
+            -8..=-1 => Ok(Self::try_from(data as i8)?.with_encoding(UnitExponentEncoding::SignedByte)),
+            0..=0xF => Self::try_from_nibble(data as u8),
+            _ => Err(UnitExponentError::InvalidData(data)),
+        }
     }
 }
 
-impl TryFrom<i8> for UnitExponent {
-    type Error = TryFromIntError;
+impl PartialEq for UnitExponent {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.encoded() == other.encoded()
+    }
+}
 
-    fn try_from(value: i8) -> Result<Self, TryFromIntError> {
-        if value >= -4 && value < 4 {
-            Ok(Self(value))
+impl TryFrom<i8> for UnitExponent {
+    type Error = UnitExponentError;
+
+    fn try_from(value: i8) -> Result<Self, UnitExponentError> {
+        if (-8..=7).contains(&value) {
+            Ok(Self {
+                value,
+                encoding: UnitExponentEncoding::default(),
+            })
         } else {
-            Err(TryFromIntError {})
+            Err(UnitExponentError::OutOfRange(value.into()))
         }
     }
 }
 
-/// A unit is divided into segments (nibbles) of 4 bits. Each segment corresponds to a different
-/// type of unit: system, length, mass, time, temperature, current, and luminosity intensity. The
-/// last segment is reserved.
-/// The value in each segment is interpreted in the table on page 37. 
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-pub struct Unit(u32);
- 
-// Coding all these units is best put into a different file.
-impl Unit {
-    pub const fn code(self) -> u32 {
-        self.0
+impl TryFrom<i32> for UnitExponent {
+    type Error = UnitExponentError;
+
+    fn try_from(value: i32) -> Result<Self, UnitExponentError> {
+        i8::try_from(value)
+            .map_err(|_| UnitExponentError::OutOfRange(value))
+            .and_then(Self::try_from)
     }
 }
 
@@ -249,3 +360,43 @@ impl Delimiter {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_unit_exponents_in_both_encodings() {
+        let nibble = UnitExponent::try_from_data(0x0F).unwrap();
+        let signed_byte = UnitExponent::try_from_data(-1).unwrap();
+        assert_eq!((nibble.as_i8(), nibble.encoded()), (-1, 0x0F));
+        assert_eq!((signed_byte.as_i8(), signed_byte.encoded()), (-1, 0xFF));
+        assert_eq!(signed_byte.encoding(), UnitExponentEncoding::SignedByte);
+        assert_ne!(nibble, signed_byte);
+        assert_eq!(nibble.with_encoding(UnitExponentEncoding::SignedByte), signed_byte);
+
+        // Non-negative exponents are written the same way in both encodings.
+        let seven = UnitExponent::try_from_data(7).unwrap();
+        assert_eq!(seven, seven.with_encoding(UnitExponentEncoding::SignedByte));
+    }
+
+    #[test]
+    fn rejects_unit_exponents_out_of_range() {
+        assert_eq!(UnitExponent::try_from(-9i8), Err(UnitExponentError::OutOfRange(-9)));
+        assert_eq!(UnitExponent::try_from(8i8), Err(UnitExponentError::OutOfRange(8)));
+        assert_eq!(UnitExponent::try_from(300i32), Err(UnitExponentError::OutOfRange(300)));
+        assert_eq!(UnitExponent::try_from_data(-9), Err(UnitExponentError::InvalidData(-9)));
+        assert_eq!(UnitExponent::try_from_data(0x10), Err(UnitExponentError::InvalidData(0x10)));
+        assert_eq!(UnitExponent::try_from_nibble(0x10), Err(UnitExponentError::InvalidData(0x10)));
+        let error: TryFromIntError = UnitExponent::try_from(8i8).unwrap_err().into();
+        assert_eq!(error, TryFromIntError {});
+    }
+
+    #[test]
+    fn writes_report_flags() {
+        assert_eq!(ReportFlags::from(0x02).to_string(), "Data,Var,Abs");
+        assert_eq!(ReportFlags::from(0x03).to_string(), "Const,Var,Abs");
+        assert_eq!(ReportFlags::from(0x1A6).to_string(), "Data,Var,Rel,No Preferred State,Volatile,Buffered Bytes");
+        assert_eq!(ReportFlags::from(0xF202).to_string(), "Data,Var,Abs,0xF200");
+    }
+}
@@ -4,11 +4,18 @@ use std::fmt::{self, Display, Formatter};
 use std::ops::{Index, IndexMut};
 use std::slice::{Iter, IterMut, SliceIndex};
 
-use super::field_types::ReportId;
+use super::field_types::{LogicalValue, PhysicalValue, ReportId, Unit, UnitExponent};
 use super::report::Report;
+use super::usage::{Usage, UsageSet};
 
 type Size = u32;
 
+/// Largest field, in bits. This is the largest Report Size accepted by the Linux HID core.
+pub const MAX_FIELD_SIZE: Size = 256;
+/// Largest report, in bits, not counting the report ID. This leaves room for the report ID in the
+/// 16384 byte report buffer of the Linux HID core.
+pub const MAX_REPORT_SIZE: Size = (16384 - 1) * 8;
+
 /// Error type when a given bit slice has bits outside the sliced range.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct DataOutOfBoundsError {}
@@ -21,6 +28,59 @@ impl Display for DataOutOfBoundsError {
 impl Error for DataOutOfBoundsError {}
 
 
+/// Error type when received report data does not match a ReportFormat.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// The report starts with a different ID than expected.
+    WrongId {
+        expected: ReportId,
+        found: u8,
+    },
+    /// The report has a different length, in bytes, than expected. Lengths include the ID.
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongId { expected, found } =>
+                write!(fmt, "expected report ID {}, found {}", expected, found),
+            Self::WrongLength { expected, found } =>
+                write!(fmt, "expected report of {} bytes, found {} bytes", expected, found),
+        }
+    }
+}
+impl Error for ParseError {}
+
+
+/// Error type when the active usages of an array cannot be written to it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArrayError {
+    /// More usages are active than the array has slots.
+    Rollover {
+        active: usize,
+        capacity: usize,
+    },
+    /// The usage cannot be selected by the array.
+    UnknownUsage(Usage),
+}
+
+impl Display for ArrayError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rollover { active, capacity } =>
+                write!(fmt, "{} usages active in an array of {} slots", active, capacity),
+            Self::UnknownUsage(usage) =>
+                write!(fmt, "usage {:#010x} is not in the array", usage.as_u32()),
+        }
+    }
+}
+impl Error for ArrayError {}
+
+
 /// Error type when a report size is larger than allowed.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct TooLargeError {}
@@ -31,11 +91,47 @@ impl Display for TooLargeError {
 }
 impl Error for TooLargeError {}
 
+
+/// Error type when a field cannot be placed in a report.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LayoutError {
+    /// The report is larger than allowed.
+    TooLarge,
+    /// A field, other than buffered bytes, is larger than MAX_FIELD_SIZE bits.
+    FieldTooLarge {
+        bit_size: Size,
+    },
+    /// A field of buffered bytes does not start and end on a byte boundary.
+    Unaligned {
+        bit_offset: Size,
+        bit_size: Size,
+    },
+}
+
+impl Display for LayoutError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge => TooLargeError {}.fmt(fmt),
+            Self::FieldTooLarge { bit_size } =>
+                write!(fmt, "field of {} bits is larger than {} bits", bit_size, MAX_FIELD_SIZE),
+            Self::Unaligned { bit_offset, bit_size } =>
+                write!(fmt, "buffered bytes of {} bits at bit {} are not byte aligned", bit_size, bit_offset),
+        }
+    }
+}
+impl Error for LayoutError {}
+
+impl From<TooLargeError> for LayoutError {
+    fn from(_: TooLargeError) -> Self {
+        Self::TooLarge
+    }
+}
+
 /// Take a slice that holds only the bytes in the given bit range.
 ///
 /// The returned slice is guaranteed to be at most the given size. It is possible that the returned
 /// slice will be less than the specified size and will need to be later padded with zeros.
-fn take_bit_slice<'a>(data: &'a [u8], bit_offset: Size, bit_size: Size) -> &'a [u8] {
+fn take_bit_slice(data: &[u8], bit_offset: Size, bit_size: Size) -> &[u8] {
     // Cast the start index of the slice to a Size.
     let first_byte: usize = match (bit_offset / 8).try_into() {
         Ok(size) => size,
@@ -84,12 +180,22 @@ fn copy_bit_slice(data: &[u8], bit_offset: Size, bit_size: Size) -> Box<[u8]> {
     // Copy the slice into a separate array.
     let mut copy = Vec::from(slice);
 
-    // If copy is zero-length, 
-    if copy.len() > 0 {
+    // Pad the copy to occupy the requisite number of bytes, counted from the first byte of the
+    // slice.
+    let bit_end = (bit_offset % 8) as u64 + bit_size as u64;
+    // If the end bit is not aligned to byte boundaries, we require an extra byte to hold the value.
+    let desired_size_u32 = bit_end.div_ceil(8);
+
+    // Panic if the slice is too large.
+    let desired_size = usize::try_from(desired_size_u32).unwrap();
+    copy.resize(desired_size, 0);
+
+    // If copy is zero-length, there are no bits to clean.
+    if !copy.is_empty() {
         // Clean the copy by zeroing extraneous bits
         
         // Remove bits from the start.
-        copy[0] &= u8::MAX << bit_offset;
+        copy[0] &= u8::MAX << (bit_offset % 8);
         // Remove bits from the end.
         let end_alignment = (bit_offset % 8 + bit_size % 8) % 8;
         // Number of bits that should be cleared from the end.
@@ -103,78 +209,236 @@ fn copy_bit_slice(data: &[u8], bit_offset: Size, bit_size: Size) -> Box<[u8]> {
         copy[last] &= u8::MAX >> end_extra_space;
     }
 
-    // Pad the copy to occupy the requisite number of bytes.
-    let bit_end = bit_offset as u64 + bit_size as u64;
-    let desired_size_u32 = if bit_end % 8 == 0 {
-        bit_end / 8
-    } else {
-        // If the end bit is not aligned to byte boundaries, we require an extra byte to hold the value.
-        bit_end / 8 + 1
-    };
-
-    // Panic if the slice is too large.
-    let desired_size = usize::try_from(desired_size_u32).unwrap();
-    copy.resize(desired_size, 0);
-
     // Box the slice
     copy.into()
 }
 
 
+/// Attributes of a field, taken from the report which declares it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FieldAttributes {
+    /// Usage of the field, if the report has any usages.
+    pub usage: Option<Usage>,
+    pub logical_minimum: LogicalValue,
+    pub logical_maximum: LogicalValue,
+    pub physical_minimum: Option<PhysicalValue>,
+    pub physical_maximum: Option<PhysicalValue>,
+    pub unit: Option<Unit>,
+    pub unit_exponent: Option<UnitExponent>,
+}
+
+impl FieldAttributes {
+    /// Construct the attributes shared by every field of a report, without a usage.
+    pub fn from_report(report: &Report) -> Self {
+        Self {
+            usage: None,
+            logical_minimum: report.logical_minimum,
+            logical_maximum: report.logical_maximum,
+            physical_minimum: report.physical_minimum,
+            physical_maximum: report.physical_maximum,
+            unit: report.unit,
+            unit_exponent: report.unit_exponent,
+        }
+    }
+
+    /// Returns true if values with these attributes are signed, which is the case when the
+    /// logical range includes negative values.
+    pub const fn is_signed(&self) -> bool {
+        self.logical_minimum < 0
+    }
+}
+
+
 /// Data specifying the byte layout of report data. Can be filled out to create a report, then
 /// sent as a report.
 /// Lower-order bits are considered first.
-/// From 8.4, a report cannot span more than 4 bytes in a report.
+///
+/// Values are held as little-endian bytes, so a variable can span any number of bits. Fields of
+/// buffered bytes are held as a single variable, in the order the bytes appear in the report.
 #[derive(Clone, Debug)]
 pub struct ReportVariable {
     bit_offset: Size,
     bit_size: Size,
-    data: u32,
+    is_signed: bool,
+    is_buffered: bool,
+    attributes: FieldAttributes,
+    data: Box<[u8]>,
 }
 
 
 impl ReportVariable {
     /// Construct a ReportVariable for a field starting at the given offset and spanning the given
-    /// size.
+    /// size, which may be at most MAX_FIELD_SIZE bits.
     /// Offset should be a global offset, in bits.
-    pub fn new(bit_offset: Size, bit_size: Size) -> Result<Self, TooLargeError> {
-        let internal_offset = bit_offset % 8;
-        if bit_size + internal_offset <= 32 {
-            Ok(Self {
-                bit_offset,
-                bit_size,
-                data: 0,
-            })
-        } else {
-            Err(TooLargeError {})
+    pub fn new(bit_offset: Size, bit_size: Size) -> Result<Self, LayoutError> {
+        if bit_size > MAX_FIELD_SIZE {
+            return Err(LayoutError::FieldTooLarge { bit_size });
         }
+        Ok(Self::with_size(bit_offset, bit_size)?)
+    }
+
+    /// Construct a ReportVariable of any size. The size must be bounded by the caller, as the
+    /// data of the variable is allocated here.
+    fn with_size(bit_offset: Size, bit_size: Size) -> Result<Self, TooLargeError> {
+        bit_offset.checked_add(bit_size).ok_or(TooLargeError {})?;
+        Ok(Self {
+            bit_offset,
+            bit_size,
+            is_signed: false,
+            is_buffered: false,
+            attributes: FieldAttributes::default(),
+            data: [0u8].repeat(bit_size.div_ceil(8) as usize).into_boxed_slice(),
+        })
+    }
+
+    /// Construct a ReportVariable which holds signed values.
+    pub fn new_signed(bit_offset: Size, bit_size: Size) -> Result<Self, LayoutError> {
+        Ok(Self {
+            is_signed: true,
+            ..Self::new(bit_offset, bit_size)?
+        })
+    }
+
+    /// Construct a ReportVariable which holds buffered bytes. Buffered bytes must start and end on
+    /// a byte boundary, and may span up to MAX_REPORT_SIZE bits.
+    pub fn new_buffered(bit_offset: Size, bit_size: Size) -> Result<Self, LayoutError> {
+        if !bit_offset.is_multiple_of(8) || !bit_size.is_multiple_of(8) {
+            return Err(LayoutError::Unaligned { bit_offset, bit_size });
+        }
+        if bit_size > MAX_REPORT_SIZE {
+            return Err(LayoutError::TooLarge);
+        }
+        Ok(Self {
+            is_buffered: true,
+            ..Self::with_size(bit_offset, bit_size)?
+        })
+    }
+
+    /// Returns this variable with the given attributes. Signedness follows the attributes.
+    pub fn with_attributes(self, attributes: FieldAttributes) -> Self {
+        Self {
+            is_signed: attributes.is_signed(),
+            attributes,
+            ..self
+        }
+    }
+
+    /// Returns the attributes of the field held by this variable.
+    pub fn attributes(&self) -> &FieldAttributes {
+        &self.attributes
+    }
+
+    /// Returns the usage of this variable, if it has one.
+    pub fn usage(&self) -> Option<Usage> {
+        self.attributes.usage
+    }
+
+    /// Returns the offset of this variable in its report, in bits, not counting the report ID.
+    pub fn bit_offset(&self) -> Size {
+        self.bit_offset
+    }
+
+    /// Returns the size of this variable, in bits.
+    pub fn bit_size(&self) -> Size {
+        self.bit_size
+    }
+
+    /// Returns true if the values of this variable are signed. A variable is signed when its
+    /// logical minimum is negative.
+    pub fn is_signed(&self) -> bool {
+        self.is_signed
+    }
+
+    /// Returns true if this variable holds buffered bytes.
+    pub fn is_buffered(&self) -> bool {
+        self.is_buffered
     }
 
     /// Clears the written data
     pub fn clear(&mut self) { 
-        self.data = 0;
+        self.data.fill(0);
     }
 
-    pub fn data(&self) -> u32 {
-        self.data
+    /// Returns the data of this variable as little-endian bytes, starting at bit 0 of the first
+    /// byte. For buffered bytes, this is the buffer in report order.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
     }
 
-    /// Copies data from a data slice. The given data slice must not have any bits outside of the
-    /// range.
-    pub fn set_unsigned(&mut self, data: u32) -> Result<(), DataOutOfBoundsError> {
-        // Check that data fits the size
-        if data & (u32::MAX << self.bit_size) == 0 {
-            let internal_offset = self.bit_offset % 8;
-            self.data = data << internal_offset;
+    /// Returns the data of this variable as little-endian bytes, allowing it to be modified.
+    /// Bits past the size of this variable are ignored.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Returns the value of this variable as an unsigned integer, truncated to 64 bits.
+    pub fn get_u64(&self) -> u64 {
+        let mut bytes = [0u8; 8];
+        let len = min(bytes.len(), self.data.len());
+        bytes[..len].copy_from_slice(&self.data[..len]);
+        u64::from_le_bytes(bytes) & u64::MAX.checked_shl(self.bit_size).map_or(u64::MAX, |mask| !mask)
+    }
+
+    /// Returns the value of this variable as a signed integer, extending the sign from its size
+    /// and truncating it to 64 bits.
+    pub fn get_i64(&self) -> i64 {
+        if self.bit_size == 0 || self.bit_size >= 64 {
+            self.get_u64() as i64
+        } else {
+            let unused_bits = 64 - self.bit_size;
+            ((self.get_u64() << unused_bits) as i64) >> unused_bits
+        }
+    }
+
+    /// Returns the value of this variable as an unsigned integer, truncated to 32 bits.
+    pub fn get_unsigned(&self) -> u32 {
+        self.get_u64() as u32
+    }
+
+    /// Returns the value of this variable as a signed integer, extending the sign from its size
+    /// and truncating it to 32 bits.
+    pub fn get_signed(&self) -> i32 {
+        self.get_i64() as i32
+    }
+
+    /// Returns the value of this variable, as a signed or unsigned integer depending on whether
+    /// this variable is signed. Unsigned values of 64 bits wrap.
+    pub fn get_value(&self) -> i64 {
+        if self.is_signed {
+            self.get_i64()
+        } else {
+            self.get_u64() as i64
+        }
+    }
 
+    /// Write the bytes of a value, filling any remaining bytes with the given byte and clearing
+    /// bits past the size of this variable.
+    fn write_le_bytes(&mut self, bytes: &[u8], fill: u8) {
+        for (index, dst) in self.data.iter_mut().enumerate() {
+            *dst = bytes.get(index).copied().unwrap_or(fill);
+        }
+        if !self.bit_size.is_multiple_of(8) {
+            if let Some(last) = self.data.last_mut() {
+                *last &= u8::MAX >> (8 - self.bit_size % 8);
+            }
+        }
+    }
+
+    /// Sets the value of this variable. The value must fit in the size of this variable.
+    pub fn set_u64(&mut self, data: u64) -> Result<(), DataOutOfBoundsError> {
+        // Check that data fits the size
+        if data & u64::MAX.checked_shl(self.bit_size).unwrap_or(0) == 0 {
+            self.write_le_bytes(&data.to_le_bytes(), 0);
             Ok(())
         } else {
             Err(DataOutOfBoundsError {})
         }
     }
 
-    pub fn set_signed(&mut self, data: i32) -> Result<(), DataOutOfBoundsError> {
-        let outside_mask = -1i32 << self.bit_size;
+    /// Sets the value of this variable from a signed integer. Negative values must fit in the
+    /// size of this variable once their sign is extended.
+    pub fn set_i64(&mut self, data: i64) -> Result<(), DataOutOfBoundsError> {
+        let outside_mask = (-1i64).checked_shl(self.bit_size).unwrap_or(0);
         // Check that the data fits the size.
         // For positive values, we expect only 0's outside the space.
         if data > 0 && data & outside_mask != 0 {
@@ -186,62 +450,312 @@ impl ReportVariable {
             return Err(DataOutOfBoundsError {})
         }
 
-        // Truncate data, in case it is negative.
-        let truncated_data = data & !outside_mask;
-        let internal_offset = self.bit_offset % 8;
-        self.data = (truncated_data as u32) << internal_offset;
-
+        let fill = if data < 0 { u8::MAX } else { 0 };
+        self.write_le_bytes(&data.to_le_bytes(), fill);
         Ok(())
     }
 
+    /// Copies data from a data slice. The given data slice must not have any bits outside of the
+    /// range.
+    pub fn set_unsigned(&mut self, data: u32) -> Result<(), DataOutOfBoundsError> {
+        self.set_u64(data.into())
+    }
+
+    pub fn set_signed(&mut self, data: i32) -> Result<(), DataOutOfBoundsError> {
+        self.set_i64(data.into())
+    }
+
+    /// Sets the value of this variable, as a signed or unsigned integer depending on whether this
+    /// variable is signed.
+    pub fn set_value(&mut self, data: i64) -> Result<(), DataOutOfBoundsError> {
+        if self.is_signed {
+            self.set_i64(data)
+        } else {
+            self.set_u64(data.try_into().map_err(|_| DataOutOfBoundsError {})?)
+        }
+    }
+
+    /// Copies little-endian bytes into this variable. The bytes must not have any bits outside of
+    /// the size of this variable.
+    pub fn set_bytes(&mut self, data: &[u8]) -> Result<(), DataOutOfBoundsError> {
+        let copy = copy_bit_slice(data, 0, self.bit_size);
+        let is_in_bounds = data.iter()
+            .zip(copy.iter().chain(std::iter::repeat(&0)))
+            .all(|(given, kept)| given == kept);
+        if is_in_bounds {
+            self.write_le_bytes(&copy, 0);
+            Ok(())
+        } else {
+            Err(DataOutOfBoundsError {})
+        }
+    }
+
     /// Construct a ReportVariable by taking the requisite bits from a slice, at an arbitrary starting
     /// position.
     /// Unlike set_data_exact, the given data can have bits out of range.
     pub fn copy_data_from_slice(&mut self, data: &[u8], bit_offset: Size) {
         // Copy bytes from data, with the original offset intact.
-        let slice = &copy_bit_slice(data, bit_offset, self.bit_size);
-        let mut data_bytes = [0u8; 8];
-        data_bytes[..slice.len()].copy_from_slice(slice);
-        let unshifted_data = u64::from_le_bytes(data_bytes);
-        // Shift bytes 
-        let target_internal_shift = self.bit_offset % 8;
-        let given_internal_shift = bit_offset % 8;
-        let shifted_data = if given_internal_shift < target_internal_shift {
-            unshifted_data << (target_internal_shift - given_internal_shift)
-        } else {
-            unshifted_data >> (given_internal_shift - target_internal_shift)
+        let slice = copy_bit_slice(data, bit_offset, self.bit_size);
+        // Shift bytes down to start at bit 0.
+        let shift = bit_offset % 8;
+        for (index, dst) in self.data.iter_mut().enumerate() {
+            let low = slice.get(index).copied().unwrap_or(0) >> shift;
+            let high = match shift {
+                0 => 0,
+                _ => slice.get(index + 1).copied().unwrap_or(0) << (8 - shift),
+            };
+            *dst = low | high;
+        }
+    }
+
+    /// OR the bits of this variable into a report, at the given offset within the report.
+    fn write_to_slice(&self, storage: &mut [u8], bit_offset: Size) {
+        let start = (bit_offset / 8) as usize;
+        let shift = bit_offset % 8;
+        let bytes = copy_bit_slice(&self.data, 0, self.bit_size);
+        for (index, byte) in bytes.iter().enumerate() {
+            if let Some(dst) = storage.get_mut(start + index) {
+                *dst |= byte << shift;
+            }
+            if shift != 0 {
+                if let Some(dst) = storage.get_mut(start + index + 1) {
+                    *dst |= byte >> (8 - shift);
+                }
+            }
+        }
+    }
+}
+
+/// An array field, in which each slot holds the index of an active usage rather than a value.
+///
+/// A value selects the usage at its position in the usage set, counted from the logical minimum.
+/// Slots with values outside the logical range, or which select a usage ID of 0, are empty.
+#[derive(Clone, Debug)]
+pub struct ReportArray {
+    slots: Vec<ReportVariable>,
+    usage_set: UsageSet,
+    attributes: FieldAttributes,
+}
+
+impl ReportArray {
+    /// Construct a ReportArray of report_count slots of the given size, starting at the given
+    /// offset.
+    /// Offset should be a global offset, in bits.
+    pub fn new(
+        bit_offset: Size,
+        bit_size: Size,
+        report_count: Size,
+        usage_set: UsageSet,
+        attributes: FieldAttributes,
+    ) -> Result<Self, LayoutError> {
+        let slots = (0..report_count)
+            .map(|index| {
+                let slot = ReportVariable::new(bit_offset + index * bit_size, bit_size)?;
+                Ok(slot.with_attributes(attributes))
+            })
+            .collect::<Result<_, LayoutError>>()?;
+        let mut array = Self {
+            slots,
+            usage_set,
+            attributes,
         };
-        self.data = shifted_data.try_into().unwrap();
+        array.clear();
+        Ok(array)
+    }
+
+    /// Returns the attributes shared by every slot of this array.
+    pub fn attributes(&self) -> &FieldAttributes {
+        &self.attributes
+    }
+
+    /// Returns the usages which can be selected by this array.
+    pub fn usage_set(&self) -> &UsageSet {
+        &self.usage_set
+    }
+
+    /// Returns the slots of this array.
+    pub fn slots(&self) -> &[ReportVariable] {
+        &self.slots
+    }
+
+    /// Returns the number of usages which can be active at once.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns true if the given usage can be selected by this array.
+    pub fn contains(&self, usage: Usage) -> bool {
+        self.value_of(usage).is_some()
+    }
+
+    /// Returns the usage selected by a value, or None if the value marks an empty slot.
+    fn usage_of(&self, value: i64) -> Option<Usage> {
+        let minimum = self.attributes.logical_minimum as i64;
+        if value < minimum || value > self.attributes.logical_maximum as i64 {
+            return None;
+        }
+        let index = u32::try_from(value - minimum).ok()?;
+        self.usage_set.get(index).filter(|usage| usage.id() != 0)
+    }
+
+    /// Returns the value which selects a usage, or None if the array cannot select it.
+    fn value_of(&self, usage: Usage) -> Option<i64> {
+        if usage.id() == 0 {
+            return None;
+        }
+        let value = self.attributes.logical_minimum as i64 + self.usage_set.position(usage)? as i64;
+        (value <= self.attributes.logical_maximum as i64).then_some(value)
+    }
+
+    /// Marks a slot as empty, preferring a value of 0 when it does not select a usage.
+    fn clear_slot(&mut self, index: usize) {
+        let minimum = self.attributes.logical_minimum as i64;
+        let maximum = self.attributes.logical_maximum as i64;
+        self.slots[index].clear();
+        for value in [0, maximum + 1, minimum - 1] {
+            if self.usage_of(value).is_none() && self.slots[index].set_value(value).is_ok() {
+                break;
+            }
+        }
+    }
+
+    /// Returns the active usages, in slot order. Empty slots are skipped.
+    pub fn usages(&self) -> Vec<Usage> {
+        self.slots.iter()
+            .filter_map(|slot| self.usage_of(slot.get_value()))
+            .collect()
+    }
+
+    /// Set the active usages, filling the remaining slots with empty values.
+    ///
+    /// Repeated usages are only written once. Fails if a usage cannot be selected or if more
+    /// usages are active than the array has slots.
+    pub fn set_usages<I: IntoIterator<Item = Usage>>(&mut self, usages: I) -> Result<(), ArrayError> {
+        let mut values = Vec::new();
+        let mut active: Vec<Usage> = Vec::new();
+        for usage in usages {
+            if active.contains(&usage) {
+                continue;
+            }
+            values.push(self.value_of(usage).ok_or(ArrayError::UnknownUsage(usage))?);
+            active.push(usage);
+        }
+        if values.len() > self.capacity() {
+            return Err(ArrayError::Rollover {
+                active: values.len(),
+                capacity: self.capacity(),
+            });
+        }
+
+        for (slot, (&value, &usage)) in self.slots.iter_mut().zip(values.iter().zip(active.iter())) {
+            slot.set_value(value).map_err(|_| ArrayError::UnknownUsage(usage))?;
+        }
+        for index in values.len()..self.capacity() {
+            self.clear_slot(index);
+        }
+        Ok(())
+    }
+
+    /// Clears all slots, so that no usages are active.
+    pub fn clear(&mut self) {
+        for index in 0..self.capacity() {
+            self.clear_slot(index);
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ItemType {
     Constant,
     Variable,
+    /// A single field of report_count buffered bytes.
+    Buffer {
+        report_count: Size,
+    },
+    /// An array of report_count slots, each selecting one usage of the usage set.
+    Array {
+        report_count: Size,
+        usage_set: UsageSet,
+    },
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ReportItem {
     pub item_type: ItemType,
     pub bit_size: Size,
+    pub attributes: FieldAttributes,
 }
 
 impl ReportItem {
-    /// Construct a ReportItem from a type and size.
+    /// Construct a ReportItem from a type and size, with default attributes.
     pub fn new(item_type: ItemType, bit_size: Size) -> Self {
         Self {
             item_type,
             bit_size,
+            attributes: FieldAttributes::default(),
         }
     }
 
+    /// Construct a ReportItem for a single field of a report, without a usage. An array report or
+    /// a report of buffered bytes is a single ReportItem holding every field.
     pub fn from_report(report: &Report) -> Self {
-        let is_constant = report.main.report_flags.is_constant();
+        let report_flags = report.main.report_flags;
+        let item_type = if report_flags.is_constant() {
+            ItemType::Constant
+        } else if report_flags.is_array() {
+            ItemType::Array {
+                report_count: report.report_count,
+                usage_set: report.usage_set.clone(),
+            }
+        } else if report_flags.is_buffered_bytes() {
+            ItemType::Buffer {
+                report_count: report.report_count,
+            }
+        } else {
+            ItemType::Variable
+        };
         ReportItem {
-            item_type: if is_constant { ItemType::Constant } else { ItemType::Variable },
+            item_type,
             bit_size: report.report_size,
+            attributes: FieldAttributes::from_report(report),
+        }
+    }
+
+    /// Construct a ReportItem for each field of a report.
+    ///
+    /// Usages are assigned to fields in order. If there are fewer usages than fields, the last
+    /// usage is repeated for the remaining fields, and extra usages are ignored, as described in
+    /// 6.2.2.8 of the USB HID specification.
+    ///
+    /// An array report is returned as a single ReportItem, as its usages are selected by value.
+    /// Buffered bytes are also returned as a single ReportItem, with the first usage.
+    pub fn from_report_fields(report: &Report) -> Vec<Self> {
+        let item = Self::from_report(report);
+        match item.item_type {
+            ItemType::Array { .. } => return vec![item],
+            ItemType::Buffer { .. } => {
+                let attributes = FieldAttributes {
+                    usage: report.usage_set.usages().next(),
+                    ..item.attributes
+                };
+                return vec![Self { attributes, ..item }];
+            },
+            _ => {},
         }
+        let mut usages = report.usage_set.usages();
+        let mut usage = None;
+        (0..report.report_count)
+            .map(|_| {
+                usage = usages.next().or(usage);
+                let attributes = FieldAttributes { usage, ..item.attributes };
+                Self { attributes, ..item.clone() }
+            })
+            .collect()
+    }
+
+    /// Returns true if the values of this item are signed.
+    pub const fn is_signed(&self) -> bool {
+        self.attributes.is_signed()
     }
 }
 
@@ -251,6 +765,9 @@ pub struct ReportFormat {
     report_id: Option<ReportId>,
     // Perhaps also support report type and ID
     reports: Vec<ReportVariable>,
+    arrays: Vec<ReportArray>,
+    /// Constant fields, kept so that received constant bits can be sent back unchanged.
+    constants: Vec<ReportVariable>,
     bit_size: Size,
 }
 
@@ -260,6 +777,8 @@ impl ReportFormat {
         Self {
             report_id: None,
             reports: Vec::new(),
+            arrays: Vec::new(),
+            constants: Vec::new(),
             bit_size: 0,
         }
     }
@@ -269,6 +788,8 @@ impl ReportFormat {
         Self {
             report_id: Some(report_id),
             reports: Vec::new(),
+            arrays: Vec::new(),
+            constants: Vec::new(),
             bit_size: 0,
         }
     }
@@ -276,8 +797,10 @@ impl ReportFormat {
     /// Construct an empty ReportFormat with an optional ID.
     pub fn new_with_opt_id(report_id: Option<ReportId>) -> Self {
         Self {
-            report_id: report_id,
+            report_id,
             reports: Vec::new(),
+            arrays: Vec::new(),
+            constants: Vec::new(),
             bit_size: 0,
         }
     }
@@ -311,27 +834,98 @@ impl ReportFormat {
         }
     }
 
+    /// Reserve the given number of bits at the end of the report, returning their offset. The
+    /// report may span up to MAX_REPORT_SIZE bits.
+    fn reserve(&mut self, bit_size: Size) -> Result<Size, TooLargeError> {
+        let bit_offset = self.bit_size;
+        self.bit_size = bit_offset.checked_add(bit_size)
+            .filter(|&bit_end| bit_end <= MAX_REPORT_SIZE)
+            .ok_or(TooLargeError {})?;
+        Ok(bit_offset)
+    }
+
     /// Add a report variable of the given size
-    pub fn push_empty(&mut self, bit_size: Size) -> Result<(), TooLargeError> {
+    pub fn push_empty(&mut self, bit_size: Size) -> Result<(), LayoutError> {
+        let variable = ReportVariable::new(self.bit_size, bit_size)?;
+        self.reserve(bit_size)?;
+        self.reports.push(variable);
+        Ok(())
+    }
+
+    /// Add a signed report variable of the given size
+    pub fn push_empty_signed(&mut self, bit_size: Size) -> Result<(), LayoutError> {
+        let variable = ReportVariable::new_signed(self.bit_size, bit_size)?;
+        self.reserve(bit_size)?;
+        self.reports.push(variable);
+        Ok(())
+    }
+
+    /// Add a report variable for the given item, keeping its attributes.
+    pub fn push_variable(&mut self, item: ReportItem) -> Result<(), LayoutError> {
+        let variable = ReportVariable::new(self.bit_size, item.bit_size)?
+            .with_attributes(item.attributes);
+        self.reserve(item.bit_size)?;
+        self.reports.push(variable);
+        Ok(())
+    }
+
+    /// Add a single variable holding buffered bytes of the given total size. The buffer must start
+    /// and end on a byte boundary.
+    pub fn push_buffer(&mut self, bit_size: Size, attributes: FieldAttributes) -> Result<(), LayoutError> {
         let bit_offset = self.bit_size;
-        self.reports.push(ReportVariable::new(bit_offset, bit_size)?);
-        self.bit_size += bit_size;
+        let variable = ReportVariable::new_buffered(bit_offset, bit_size)?
+            .with_attributes(attributes);
+        self.reserve(bit_size)?;
+        self.reports.push(variable);
+        Ok(())
+    }
+
+    /// Add an array of report_count slots of the given size.
+    pub fn push_array(
+        &mut self,
+        bit_size: Size,
+        report_count: Size,
+        usage_set: UsageSet,
+        attributes: FieldAttributes,
+    ) -> Result<(), LayoutError> {
+        let total_size = bit_size.checked_mul(report_count).ok_or(TooLargeError {})?;
+        let bit_offset = self.reserve(total_size)?;
+        self.arrays.push(ReportArray::new(bit_offset, bit_size, report_count, usage_set, attributes)?);
         Ok(())
     }
 
-    /// Add a constant report variable of the specified size
-    pub fn push_constant(&mut self, bit_size: Size) {
-        self.bit_size += bit_size;
+    /// Add a constant report variable of the specified size. Constants are never read as values,
+    /// so they are only bounded by MAX_REPORT_SIZE.
+    pub fn push_constant(&mut self, bit_size: Size) -> Result<(), LayoutError> {
+        let bit_offset = self.reserve(bit_size)?;
+        self.constants.push(ReportVariable::with_size(bit_offset, bit_size)?);
+        Ok(())
+    }
+
+    /// Add a constant to the end of the report so that it ends on a byte boundary, as done by
+    /// PaddingMode::EndOfReport when a Collection is compiled.
+    pub fn pad_to_byte_boundary(&mut self) -> Result<(), LayoutError> {
+        match self.bit_size % 8 {
+            0 => Ok(()),
+            bits => self.push_constant(8 - bits),
+        }
     }
 
-    pub fn copy_from_iter<I: Iterator<Item = ReportItem>>(mut self, items: I) -> Result<Self, TooLargeError> {
+    pub fn copy_from_iter<I: Iterator<Item = ReportItem>>(mut self, items: I) -> Result<Self, LayoutError> {
         for item in items {
             match item.item_type {
                 ItemType::Constant => {
-                    self.push_constant(item.bit_size);
+                    self.push_constant(item.bit_size)?;
                 },
                 ItemType::Variable => {
-                    self.push_empty(item.bit_size)?;
+                    self.push_variable(item)?;
+                },
+                ItemType::Buffer { report_count } => {
+                    let bit_size = item.bit_size.checked_mul(report_count).ok_or(LayoutError::TooLarge)?;
+                    self.push_buffer(bit_size, item.attributes)?;
+                },
+                ItemType::Array { report_count, usage_set } => {
+                    self.push_array(item.bit_size, report_count, usage_set, item.attributes)?;
                 },
             }
         }
@@ -348,6 +942,51 @@ impl ReportFormat {
         self.reports.iter_mut()
     }
 
+    /// Returns the first variable with the given usage.
+    pub fn field(&self, usage: Usage) -> Option<&ReportVariable> {
+        self.reports.iter().find(|variable| variable.usage() == Some(usage))
+    }
+
+    /// Returns the first variable with the given usage, allowing it to be modified.
+    pub fn field_mut(&mut self, usage: Usage) -> Option<&mut ReportVariable> {
+        self.reports.iter_mut().find(|variable| variable.usage() == Some(usage))
+    }
+
+    /// Returns an iterator over all variables with the given usage.
+    pub fn fields(&self, usage: Usage) -> impl Iterator<Item = &ReportVariable> {
+        self.reports.iter().filter(move |variable| variable.usage() == Some(usage))
+    }
+
+    /// Returns an iterator that allows modifying all variables with the given usage.
+    pub fn fields_mut(&mut self, usage: Usage) -> impl Iterator<Item = &mut ReportVariable> {
+        self.reports.iter_mut().filter(move |variable| variable.usage() == Some(usage))
+    }
+
+    /// Returns an iterator over the constant fields, such as padding.
+    pub fn constants(&self) -> Iter<'_, ReportVariable> {
+        self.constants.iter()
+    }
+
+    /// Returns an iterator over the contained arrays.
+    pub fn arrays(&self) -> Iter<'_, ReportArray> {
+        self.arrays.iter()
+    }
+
+    /// Returns an iterator that allows modifying the contained arrays.
+    pub fn arrays_mut(&mut self) -> IterMut<'_, ReportArray> {
+        self.arrays.iter_mut()
+    }
+
+    /// Returns the first array which can select the given usage.
+    pub fn array(&self, usage: Usage) -> Option<&ReportArray> {
+        self.arrays.iter().find(|array| array.contains(usage))
+    }
+
+    /// Returns the first array which can select the given usage, allowing it to be modified.
+    pub fn array_mut(&mut self, usage: Usage) -> Option<&mut ReportArray> {
+        self.arrays.iter_mut().find(|array| array.contains(usage))
+    }
+
     /// Returns the number of reports in this ReportFormat.
     pub fn count(&self) -> u32 {
         self.reports.len().try_into().unwrap()
@@ -358,33 +997,76 @@ impl ReportFormat {
         for report in self.iter_mut() {
             report.clear()
         }
+        for array in self.arrays.iter_mut() {
+            array.clear()
+        }
+        for constant in self.constants.iter_mut() {
+            constant.clear()
+        }
+    }
+
+    /// Returns the size of the report, in bits, including the ID.
+    pub fn bit_len(&self) -> u64 {
+        let id_size: u64 = if self.report_id.is_some() { 8 } else { 0 };
+        id_size + u64::from(self.bit_size)
+    }
+
+    /// Returns the size of the report, in bytes, including the ID.
+    pub fn byte_len(&self) -> usize {
+        let id_size: u64 = if self.report_id.is_some() { 1 } else { 0 };
+        usize::try_from(id_size + (self.bit_size as u64).div_ceil(8)).unwrap()
+    }
+
+    /// Fill this ReportFormat from a received report, including its ID.
+    ///
+    /// Fails if the report does not start with this format's ID or does not have the expected
+    /// length. Constant fields are kept, so that the report is reproduced exactly by into_bytes.
+    pub fn parse(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
+        if bytes.len() != self.byte_len() {
+            return Err(ParseError::WrongLength {
+                expected: self.byte_len(),
+                found: bytes.len(),
+            });
+        }
+        let data = match self.report_id {
+            Some(report_id) => {
+                if bytes[0] != report_id {
+                    return Err(ParseError::WrongId {
+                        expected: report_id,
+                        found: bytes[0],
+                    });
+                }
+                &bytes[1..]
+            },
+            None => bytes,
+        };
+
+        let slots = self.arrays.iter_mut().flat_map(|array| array.slots.iter_mut());
+        for variable in self.reports.iter_mut().chain(slots).chain(self.constants.iter_mut()) {
+            let bit_offset = variable.bit_offset;
+            variable.copy_data_from_slice(data, bit_offset);
+        }
+        Ok(())
     }
 
     /// Convert a filled ReportFormat into bytes.
     /// Unfilled reports items are assigned 0.
     pub fn into_bytes(self) -> Box<[u8]> {
-        let id_size: u32 = if self.report_id.is_some() { 1 } else { 0 };
-        let size_in_bytes = usize::try_from(
-            id_size as u64 + (self.bit_size as u64 + 7) / 8).unwrap();
+        let size_in_bytes = self.byte_len();
         let mut storage = [0u8].repeat(size_in_bytes);
         // Prepend the ID
-        if let Some(report_id) = self.report_id {
-            storage[0] = report_id;
-        }
+        let data = match self.report_id {
+            Some(report_id) => {
+                storage[0] = report_id;
+                &mut storage[1..]
+            },
+            None => &mut storage[..],
+        };
         
         // Copy each report into storage.
-        for report_item in self.iter() {
-            let report_data = report_item.data();
-            // Calculate the starting byte of this item, including any offset due to the report id.
-            let start = (id_size + report_item.bit_offset / 8).try_into().unwrap();
-            let end = min(start + 4, size_in_bytes);
-            let len = end - start;
-            // Copy bits from report_data into storage
-            // Copy bits until the end of storage is reached
-            let data_bytes = report_data.to_le_bytes();
-            for (dst, src) in storage[start..end].iter_mut().zip(data_bytes[..len].iter()) {
-                *dst |= src;
-            }
+        let slots = self.arrays.iter().flat_map(|array| array.slots.iter());
+        for report_item in self.iter().chain(slots).chain(self.constants.iter()) {
+            report_item.write_to_slice(data, report_item.bit_offset);
         }
 
         storage.into_boxed_slice()
@@ -406,3 +1088,126 @@ impl<I: SliceIndex<[ReportVariable]>> IndexMut<I> for ReportFormat {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::ReportFlags;
+
+    #[test]
+    fn packs_fields_across_byte_boundaries() {
+        let mut format = ReportFormat::new_with_id(3);
+        format.push_empty(3).unwrap();
+        format.push_empty_signed(12).unwrap();
+        format.push_empty(1).unwrap();
+        format[0].set_unsigned(5).unwrap();
+        format[1].set_signed(-2).unwrap();
+        format[2].set_unsigned(1).unwrap();
+        assert_eq!(format[0].set_unsigned(8), Err(DataOutOfBoundsError {}));
+        assert_eq!(format.byte_len(), 3);
+
+        let bytes = format.clone().into_bytes();
+        assert_eq!(&*bytes, &[0x03, 0xF5, 0xFF]);
+
+        let mut parsed = format.clone();
+        parsed.clear();
+        parsed.parse(&bytes).unwrap();
+        assert_eq!((parsed[0].get_unsigned(), parsed[1].get_signed(), parsed[2].get_unsigned()), (5, -2, 1));
+        assert_eq!(parsed.parse(&[0x04, 0, 0]), Err(ParseError::WrongId { expected: 3, found: 4 }));
+        assert_eq!(parsed.parse(&[0x03, 0]), Err(ParseError::WrongLength { expected: 3, found: 2 }));
+    }
+
+    #[test]
+    fn repeats_the_last_usage_for_remaining_fields() {
+        let (x, y) = (Usage::new(0x01, 0x30), Usage::new(0x01, 0x31));
+        let usage_set = UsageSet::empty().with_usage(x).with_usage(y);
+        let report = Report::new_input(ReportFlags::new().as_variable(), usage_set, -127, 127, 8, 3);
+        let items = ReportItem::from_report_fields(&report);
+        let mut format = ReportFormat::new().copy_from_iter(items.into_iter()).unwrap();
+        assert_eq!(format.iter().map(ReportVariable::usage).collect::<Vec<_>>(), [Some(x), Some(y), Some(y)]);
+        assert_eq!(format.fields(y).count(), 2);
+
+        let field = format.field_mut(x).unwrap();
+        assert!(field.is_signed());
+        assert_eq!(field.attributes().logical_minimum, -127);
+        field.set_signed(-1).unwrap();
+        assert_eq!(&*format.into_bytes(), &[0xFF, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn rejects_oversized_fields_before_allocating() {
+        assert!(ReportVariable::new(0, MAX_FIELD_SIZE).is_ok());
+        assert_eq!(ReportVariable::new(0, MAX_FIELD_SIZE + 1).unwrap_err(),
+            LayoutError::FieldTooLarge { bit_size: MAX_FIELD_SIZE + 1 });
+        assert_eq!(ReportVariable::new(0, Size::MAX).unwrap_err(),
+            LayoutError::FieldTooLarge { bit_size: Size::MAX });
+
+        // Buffered bytes are bounded by the size of a report instead.
+        assert_eq!(ReportVariable::new_buffered(0, 64 * 8).unwrap().as_bytes().len(), 64);
+        assert_eq!(ReportVariable::new_buffered(0, MAX_REPORT_SIZE + 8).unwrap_err(), LayoutError::TooLarge);
+
+        let items = [ReportItem::new(ItemType::Variable, Size::MAX)];
+        let error = ReportFormat::new().copy_from_iter(items.into_iter()).unwrap_err();
+        assert_eq!(error, LayoutError::FieldTooLarge { bit_size: Size::MAX });
+        let mut format = ReportFormat::new();
+        assert!(format.push_constant(MAX_REPORT_SIZE + 1).is_err());
+        assert_eq!(format.byte_len(), 0);
+        let items = [ReportItem::new(ItemType::Buffer { report_count: Size::MAX / 8 }, 8)];
+        assert!(ReportFormat::new().copy_from_iter(items.into_iter()).is_err());
+    }
+
+    #[test]
+    fn bounds_constants_by_the_report_size() {
+        let items = [ReportItem::new(ItemType::Variable, 8), ReportItem::new(ItemType::Constant, 4 * MAX_FIELD_SIZE)];
+        let format = ReportFormat::new().copy_from_iter(items.into_iter()).unwrap();
+        assert_eq!(format.byte_len(), 129);
+        assert_eq!(format.constants.iter().map(ReportVariable::bit_size).collect::<Vec<_>>(), [4 * MAX_FIELD_SIZE]);
+
+        let mut format = ReportFormat::new();
+        format.push_constant(MAX_REPORT_SIZE).unwrap();
+        assert_eq!(format.push_constant(1), Err(LayoutError::TooLarge));
+    }
+
+    #[test]
+    fn holds_wide_fields_and_buffered_bytes() {
+        let mut format = ReportFormat::new();
+        format.push_empty(4).unwrap();
+        format.push_empty_signed(40).unwrap();
+        // Buffered bytes start on a byte boundary.
+        assert_eq!(format.push_buffer(24, FieldAttributes::default()),
+                   Err(LayoutError::Unaligned { bit_offset: 44, bit_size: 24 }));
+        format.push_constant(4).unwrap();
+        format.push_buffer(24, FieldAttributes::default()).unwrap();
+        format[1].set_i64(-(1 << 38)).unwrap();
+        assert_eq!(format[1].set_i64(1 << 40), Err(DataOutOfBoundsError {}));
+        format[2].set_bytes(&[0xAB, 0xCD, 0xEF]).unwrap();
+        assert_eq!(format[2].set_bytes(&[0, 0, 0, 1]), Err(DataOutOfBoundsError {}));
+        assert_eq!(format.byte_len(), 9);
+
+        let bytes = format.clone().into_bytes();
+        let mut parsed = format.clone();
+        parsed.clear();
+        parsed.parse(&bytes).unwrap();
+        assert_eq!(parsed[1].get_i64(), -(1 << 38));
+        assert!(parsed[2].is_buffered());
+        assert_eq!(parsed[2].as_bytes(), &[0xAB, 0xCD, 0xEF]);
+    }
+
+    #[test]
+    fn selects_array_usages_by_value() {
+        let usage_set = UsageSet::empty().with_usage_bounds(Usage::new(0x07, 0x04), Usage::new(0x07, 0x1D));
+        let attributes = FieldAttributes { logical_minimum: 4, logical_maximum: 29, ..FieldAttributes::default() };
+        let items = [ReportItem {
+            item_type: ItemType::Array { report_count: 2, usage_set },
+            bit_size: 8,
+            attributes,
+        }];
+        let mut format = ReportFormat::new().copy_from_iter(items.into_iter()).unwrap();
+        let array = format.arrays_mut().next().unwrap();
+        array.set_usages([Usage::new(0x07, 0x05)]).unwrap();
+        assert_eq!(array.usages(), vec![Usage::new(0x07, 0x05)]);
+        let error = array.set_usages([Usage::new(0x07, 0x04), Usage::new(0x07, 0x05), Usage::new(0x07, 0x06)]);
+        assert_eq!(error, Err(ArrayError::Rollover { active: 3, capacity: 2 }));
+        assert_eq!(&*format.into_bytes(), &[0x05, 0x00]);
+    }
+}
@@ -0,0 +1,261 @@
+//! Import of report descriptors from the textual forms they are usually found in: C arrays in
+//! firmware sources, and hex dumps such as those of usbhid-dump or /sys/kernel/debug/hid/*/rdesc.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use super::decompiler::{DecompileError, Decompiler};
+use super::descriptor::ReportDescriptor;
+use super::error::DecodeError;
+
+/// Error type when a report descriptor cannot be imported from text.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ImportError {
+    /// An array holds a value which is not a byte. Lines and columns start at 1.
+    InvalidToken {
+        line: usize,
+        column: usize,
+    },
+    /// The text holds no bytes.
+    NoData,
+    /// The bytes are not a sequence of items.
+    Decode(DecodeError),
+    /// The items do not form a report descriptor.
+    Decompile(DecompileError),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidToken { line, column } =>
+                write!(fmt, "invalid byte at line {}, column {}", line, column),
+            Self::NoData => "no descriptor bytes found".fmt(fmt),
+            Self::Decode(error) => error.fmt(fmt),
+            Self::Decompile(error) => error.fmt(fmt),
+        }
+    }
+}
+impl Error for ImportError {}
+
+impl From<DecodeError> for ImportError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+impl From<DecompileError> for ImportError {
+    fn from(error: DecompileError) -> Self {
+        Self::Decompile(error)
+    }
+}
+
+/// Replace comments with spaces, keeping every line and column in place. Both C comments and
+/// comments starting with '#' are removed.
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_block = false;
+    let mut in_line = false;
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            in_line = false;
+            stripped.push(c);
+        } else if in_block {
+            if c == '*' && chars.peek() == Some(&'/') {
+                chars.next();
+                in_block = false;
+                stripped.push(' ');
+            }
+            stripped.push(' ');
+        } else if in_line {
+            stripped.push(' ');
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            in_block = true;
+            stripped.push_str("  ");
+        } else if (c == '/' && chars.peek() == Some(&'/')) || c == '#' {
+            in_line = true;
+            stripped.push(' ');
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Remove the ASCII column of hexdump -C, from the first '|' of each line, keeping every line in
+/// place. The column may hold any characters, including the start of a comment, so it is removed
+/// before comments are.
+fn without_ascii_column(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split('|').next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Keep only the initializer of an array, between its outermost braces or brackets, keeping every
+/// line and column in place. The initializer follows the first '=', if any, so that the
+/// declaration may be included, as in `static const uint8_t desc[] = {` or
+/// `const DESC: [u8; 4] = [`.
+fn array_body(text: &str) -> String {
+    let start = text.find('=').map_or(0, |offset| offset + 1);
+    let mut depth = 0;
+    text.char_indices()
+        .map(|(offset, c)| {
+            let is_opening = offset >= start && matches!(c, '{' | '[');
+            let is_closing = offset >= start && matches!(c, '}' | ']');
+            let is_body = depth > 0 && !(depth == 1 && is_closing);
+            if is_opening {
+                depth += 1;
+            } else if is_closing && depth > 0 {
+                depth -= 1;
+            }
+            if is_body || c == '\n' { c } else { ' ' }
+        })
+        .collect()
+}
+
+/// Split a line into tokens, with the column of each, starting at 1.
+fn tokens(line: &str) -> impl Iterator<Item = (usize, &str)> + '_ {
+    line.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|token| !token.is_empty())
+        .map(move |token| {
+            let offset = token.as_ptr() as usize - line.as_ptr() as usize;
+            (line[..offset].chars().count() + 1, token)
+        })
+}
+
+/// Parse a byte of a C array, written in hexadecimal with a "0x" prefix, or in decimal.
+fn parse_c_byte(token: &str) -> Option<u8> {
+    match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(digits) => u8::from_str_radix(digits, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+/// Parse a byte of a hex dump, written as one or two hexadecimal digits.
+fn parse_dump_byte(token: &str) -> Option<u8> {
+    let digits = if has_hex_prefix(token) { &token[2..] } else { token };
+    if digits.len() <= 2 {
+        u8::from_str_radix(digits, 16).ok()
+    } else {
+        None
+    }
+}
+
+/// Returns true if a token is an address column, such as "0010:" or "00000010", or a header
+/// such as the "001:003:000:DESCRIPTOR" of usbhid-dump.
+fn is_address(token: &str) -> bool {
+    token.contains(':') || (token.len() > 2 && token.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Extract the bytes of an array initializer. Every value must be a byte.
+fn c_array_bytes(text: &str) -> Result<Vec<u8>, ImportError> {
+    let mut bytes = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        for (column, token) in tokens(line) {
+            let byte = parse_c_byte(token)
+                .ok_or(ImportError::InvalidToken { line: index + 1, column })?;
+            bytes.push(byte);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Extract the bytes of a hex dump.
+///
+/// Each line may start with an address column, and ends at the first token which is not a byte,
+/// so that annotations and timestamps are ignored. The dump ends at the first blank line after
+/// its bytes, which in rdesc files separates the bytes from the decoded reports.
+fn hex_dump_bytes(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() && !bytes.is_empty() {
+            break;
+        }
+        let mut line_tokens = tokens(line).map(|(_, token)| token).peekable();
+        if line_tokens.peek().is_some_and(|token| is_address(token)) {
+            line_tokens.next();
+        }
+        bytes.extend(line_tokens.map_while(parse_dump_byte));
+    }
+    bytes
+}
+
+/// Returns true if a token is written in hexadecimal with a "0x" prefix.
+fn has_hex_prefix(token: &str) -> bool {
+    token.starts_with("0x") || token.starts_with("0X")
+}
+
+/// Extract the bytes of a report descriptor written as a C array or as a hex dump.
+///
+/// Text starting with a "0x" value is read as the values of an array, and text starting with a
+/// byte or an address column is read as a hex dump. Other text is read as an array declaration,
+/// such as a C array or a Rust constant. Values of an array are bytes in hexadecimal with a "0x"
+/// prefix or in decimal. Comments are ignored in every form, and the ASCII column of hexdump -C
+/// is ignored in hex dumps.
+pub fn extract_bytes(text: &str) -> Result<Box<[u8]>, ImportError> {
+    let stripped = strip_comments(text);
+    let bytes = match tokens(&stripped).next() {
+        Some((_, token)) if has_hex_prefix(token) => c_array_bytes(&stripped)?,
+        Some((_, token)) if parse_dump_byte(token).is_some() || is_address(token) =>
+            hex_dump_bytes(&strip_comments(&without_ascii_column(text))),
+        _ => c_array_bytes(&array_body(&stripped))?,
+    };
+    if bytes.is_empty() {
+        return Err(ImportError::NoData);
+    }
+    Ok(bytes.into_boxed_slice())
+}
+
+impl ReportDescriptor {
+    /// Import a report descriptor from a C array or a hex dump, as by extract_bytes.
+    pub fn import(text: &str) -> Result<Self, ImportError> {
+        let bytes = extract_bytes(text)?;
+        Ok(Decompiler::from_bytes(&bytes)?.descriptor()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::item::Items;
+
+    // Usage Page (Generic Desktop), Usage (Mouse), Collection (Application), End Collection
+    const BYTES: [u8; 7] = [0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0xC0];
+
+    fn items() -> Items {
+        Items::from_bytes(&BYTES).unwrap()
+    }
+
+    #[test]
+    fn imports_exported_arrays() {
+        assert_eq!(&*extract_bytes(&items().to_c_array("descriptor")).unwrap(), &BYTES);
+        assert_eq!(&*extract_bytes(&items().to_rust_const("DESCRIPTOR")).unwrap(), &BYTES);
+        assert_eq!(&*extract_bytes(&items().to_hex_dump()).unwrap(), &BYTES);
+    }
+
+    #[test]
+    fn imports_arrays_with_comments() {
+        let text = "/* mouse */\nstatic const uint8_t desc[] = {\n    0x05, 0x01, // Usage Page\n    9, 2,\n    0xA1, 0x01, 0xC0 };";
+        assert_eq!(&*extract_bytes(text).unwrap(), &BYTES);
+        assert_eq!(extract_bytes("0x05, 0x01,\n 0x100"), Err(ImportError::InvalidToken { line: 2, column: 2 }));
+    }
+
+    #[test]
+    fn imports_tool_dumps() {
+        let rdesc = "05 01 09 02 a1 01 c0\n\n  INPUT[INPUT]\n    Field(0)\n";
+        assert_eq!(&*extract_bytes(rdesc).unwrap(), &BYTES);
+        let usbhid_dump = "001:003:000:DESCRIPTOR         1700000000.000000\n 05 01 09 02 A1 01 C0\n";
+        assert_eq!(&*extract_bytes(usbhid_dump).unwrap(), &BYTES);
+    }
+
+    #[test]
+    fn ignores_comments_in_the_ascii_column_of_hexdump() {
+        let text = "00000000  05 01 09 02 a1 01 2f 2a  |....../*|\n00000008  c0                       |.|\n00000009\n";
+        let mut bytes = BYTES.to_vec();
+        bytes.insert(6, 0x2F);
+        bytes.insert(7, 0x2A);
+        assert_eq!(&*extract_bytes(text).unwrap(), &bytes[..]);
+    }
+}
@@ -1,20 +1,55 @@
 use super::collection::Collection;
+use super::descriptor::ReportDescriptor;
 use super::optimizer::TagOptimizer;
-use super::item::{ShortItems};
+use super::padding::PaddingMode;
+use super::item::Items;
 use super::tag::{Tag, TagGroup};
 
 impl Collection {
+    /// Compile this collection into a report descriptor.
+    ///
+    /// Each report type and ID is padded to end on a byte boundary, matching the ReportFormats
+    /// built from this collection. Padding in another mode should be done beforehand with
+    /// Collection::padded.
     pub fn into_bytes(self) -> Box<[u8]> {
-        // Convert a report into a tree of tags.
-        let tag_groups = TagGroup::collection(self);
+        ReportDescriptor::from(self).into_bytes()
+    }
+
+    /// Compile this collection into the tags of a report descriptor, as by Collection::into_bytes.
+    pub fn into_tags(self) -> Box<[Tag]> {
+        ReportDescriptor::from(self).into_tags()
+    }
+}
+
+impl ReportDescriptor {
+    /// Compile every top-level collection into a single report descriptor.
+    ///
+    /// Global items are shared across the collections, so a global item is only repeated when its
+    /// value changes. Padding is done as by Collection::into_bytes, over all collections.
+    pub fn into_bytes(self) -> Box<[u8]> {
+        // Compile tags down into Items
+        let tag_items = Items::from_iter(self.into_tags().into_vec());
+        // Convert Items to bytes
+        tag_items.into_bytes()
+    }
+
+    /// Compile every top-level collection into the tags of a report descriptor, as by
+    /// ReportDescriptor::into_bytes.
+    pub fn into_tags(self) -> Box<[Tag]> {
+        // Pad each report to a byte boundary.
+        let padded = self.padded(PaddingMode::EndOfReport);
+        // Merge reports which only differ by usage, and convert each collection into a tree of
+        // tags.
+        let tag_groups = TagGroup::group(padded.collections
+            .into_vec()
+            .into_iter()
+            .map(|collection| TagGroup::collection(collection.merge_reports())));
         // Linearize tag structure
         let tags: Vec<Tag> = tag_groups.tags().cloned().collect();
-        // Remove duplicate tags
+        // Choose usage encodings and remove duplicate tags
         let tags_cleaned = TagOptimizer::from_iter(tags)
+            .optimize_usages()
             .remove_duplicates();
-        // Compile tags down into ShortItems
-        let tag_items = ShortItems::from_iter(tags_cleaned);
-        // Convert ShortItems to bytes
-        tag_items.into_bytes()
+        tags_cleaned.into_iter().collect()
     }
 }
@@ -1,5 +1,9 @@
-use super::error::TryFromIntError;
+use std::slice::Iter;
+
+use super::error::{DecodeError, ItemError, TryFromIntError};
+use super::field_types::{CollectionType, Delimiter, UnitExponent};
 use super::tag::Tag;
+use super::usage::ExtendedUsage;
 
 /// A BSize represents the two-bit size code of a report descriptor item.
 pub enum BSize {
@@ -129,6 +133,11 @@ impl SizeTypeTag {
     pub const fn as_u8(self) -> u8 {
         self.0
     }
+
+    /// Returns the type and tag of this item, without its size.
+    pub const fn prefix(self) -> ItemPrefix {
+        ItemPrefix::from_u8(self.0)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -139,6 +148,50 @@ impl ShortItem {
         Self(size_type_tag, data)
     }
 
+    /// Returns the size, type, and tag of this item.
+    pub const fn size_type_tag(&self) -> SizeTypeTag {
+        self.0
+    }
+
+    /// Returns the type and tag of this item.
+    pub const fn prefix(&self) -> ItemPrefix {
+        self.0.prefix()
+    }
+
+    /// Returns the data of this item, zero-extended.
+    pub const fn data(&self) -> u32 {
+        self.1
+    }
+
+    /// Returns the data of this item, sign-extended from its size.
+    pub fn signed_data(&self) -> i32 {
+        match self.0.size() {
+            BSize::B0 => 0,
+            BSize::B1 => self.1 as u8 as i8 as i32,
+            BSize::B2 => self.1 as u16 as i16 as i32,
+            BSize::B4 => self.1 as i32,
+        }
+    }
+
+    /// Returns the number of bytes used to encode this item.
+    pub fn encoded_len(&self) -> usize {
+        1 + self.0.size().size() as usize
+    }
+
+    /// Read a ShortItem from the start of a slice. Fails on the prefix of a long item.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ItemError> {
+        let size_type_tag = match bytes.first() {
+            Some(&LONG_ITEM_PREFIX) => return Err(ItemError::UnknownTag(LONG_ITEM_PREFIX)),
+            Some(&prefix) => SizeTypeTag::from_u8(prefix),
+            None => return Err(ItemError::Truncated),
+        };
+        let size = size_type_tag.size().size() as usize;
+        let data_bytes = bytes.get(1..1 + size).ok_or(ItemError::Truncated)?;
+        let mut data = [0u8; 4];
+        data[..size].copy_from_slice(data_bytes);
+        Ok(Self(size_type_tag, u32::from_le_bytes(data)))
+    }
+
     pub fn into_bytes(self) -> Box<[u8]> {
         let mut data = [0u8; 5];
         data[0] = self.0.as_u8();
@@ -293,6 +346,108 @@ impl ItemPrefix {
 }
 
 
+/// Prefix byte of every long item.
+pub const LONG_ITEM_PREFIX: u8 = 0xFE;
+
+/// A long item carries up to 255 bytes of data under an 8-bit tag. No long item tags are defined
+/// by the USB HID specification, so long items are only used by vendors.
+/// 6.2.2.3 of the USB HID specification
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LongItem {
+    tag: u8,
+    data: Box<[u8]>,
+}
+
+impl LongItem {
+    /// Construct a LongItem from a tag and its data. Fails if the data is longer than 255 bytes.
+    pub fn new(tag: u8, data: &[u8]) -> Result<Self, TryFromIntError> {
+        u8::try_from(data.len()).map_err(|_| TryFromIntError {})?;
+        Ok(Self {
+            tag,
+            data: data.into(),
+        })
+    }
+
+    /// Returns the tag of this item.
+    pub const fn tag(&self) -> u8 {
+        self.tag
+    }
+
+    /// Returns the data of this item.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the number of bytes used to encode this item.
+    pub fn encoded_len(&self) -> usize {
+        3 + self.data.len()
+    }
+
+    /// Read a LongItem from the start of a slice.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ItemError> {
+        match bytes {
+            [LONG_ITEM_PREFIX, size, tag, rest @ ..] => {
+                let data = rest.get(..*size as usize).ok_or(ItemError::Truncated)?;
+                Ok(Self::new(*tag, data).unwrap())
+            },
+            [LONG_ITEM_PREFIX, ..] | [] => Err(ItemError::Truncated),
+            [prefix, ..] => Err(ItemError::UnknownTag(*prefix)),
+        }
+    }
+
+    pub fn into_bytes(self) -> Box<[u8]> {
+        // The length of the data is checked on construction.
+        [LONG_ITEM_PREFIX, self.data.len() as u8, self.tag].iter()
+            .chain(self.data.iter())
+            .copied()
+            .collect()
+    }
+}
+
+/// An Item is a single short or long item of a report descriptor.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Item {
+    Short(ShortItem),
+    Long(LongItem),
+}
+
+impl Item {
+    /// Returns the number of bytes used to encode this item.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Short(item) => item.encoded_len(),
+            Self::Long(item) => item.encoded_len(),
+        }
+    }
+
+    /// Read an Item from the start of a slice.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ItemError> {
+        match bytes.first() {
+            Some(&LONG_ITEM_PREFIX) => LongItem::from_bytes(bytes).map(Self::Long),
+            _ => ShortItem::from_bytes(bytes).map(Self::Short),
+        }
+    }
+
+    pub fn into_bytes(self) -> Box<[u8]> {
+        match self {
+            Self::Short(item) => item.into_bytes(),
+            Self::Long(item) => item.into_bytes(),
+        }
+    }
+}
+
+impl From<ShortItem> for Item {
+    fn from(item: ShortItem) -> Self {
+        Self::Short(item)
+    }
+}
+
+impl From<LongItem> for Item {
+    fn from(item: LongItem) -> Self {
+        Self::Long(item)
+    }
+}
+
 // 6.2.2.4 of the USB HID specification
 pub mod main_item {
     use super::ItemPrefix;
@@ -336,9 +491,9 @@ pub mod local_item {
 }
 
 
-impl From<Tag> for ShortItem {
+impl From<Tag> for Item {
     fn from(tag: Tag) -> Self {
-        match tag {
+        let short_item = match tag {
             // Main items
             Tag::Input(input) => main_item::INPUT.with_shrunk_u32(input.into()),
             Tag::Output(output) =>
@@ -362,7 +517,7 @@ impl From<Tag> for ShortItem {
             Tag::PhysicalMaximum(physical_maximum) =>
                 global_item::PHYSICAL_MAXIMUM.with_shrunk_i32(physical_maximum),
             Tag::UnitExponent(unit_exponent) =>
-                global_item::UNIT_EXPONENT.with_u8(unit_exponent.as_nibble()),
+                global_item::UNIT_EXPONENT.with_u8(unit_exponent.encoded()),
             Tag::Unit(unit) =>
                 global_item::UNIT.with_shrunk_u32(unit.code()),
             Tag::ReportSize(report_size) =>
@@ -401,21 +556,95 @@ impl From<Tag> for ShortItem {
                 local_item::STRING_MAXIMUM.with_shrunk_u32(string_maximum.into()),
             Tag::Delimiter(delimiter) =>
                 local_item::DELIMITER.with_shrunk_u32(if delimiter.is_open() { 1 } else { 0 }),
+
+            // Long items pass through unchanged.
+            Tag::LongItem(long_item) => return Self::Long(long_item),
+        };
+        Self::Short(short_item)
+    }
+}
+
+impl TryFrom<Tag> for ShortItem {
+    type Error = ItemError;
+
+    /// Convert a Tag into a ShortItem. Fails if the Tag holds a long item.
+    fn try_from(tag: Tag) -> Result<Self, ItemError> {
+        match Item::from(tag) {
+            Item::Short(short_item) => Ok(short_item),
+            Item::Long(_) => Err(ItemError::UnknownTag(LONG_ITEM_PREFIX)),
         }
     }
 }
  
-/// A sequence of ShortItems
-#[derive(Clone, Debug, Default)]
-pub struct ShortItems(Vec<ShortItem>);
+impl TryFrom<ShortItem> for Tag {
+    type Error = ItemError;
+
+    /// Interpret a ShortItem as a Tag. This is the reverse of converting a Tag into a ShortItem.
+    fn try_from(item: ShortItem) -> Result<Self, ItemError> {
+        let invalid = ItemError::InvalidData(item.size_type_tag().as_u8());
+        let data = item.data();
+        let is_extended = matches!(item.size_type_tag().size(), BSize::B4);
+        let tag = match item.prefix() {
+            // Main items
+            main_item::INPUT => Tag::Input(data.into()),
+            main_item::OUTPUT => Tag::Output(data.into()),
+            main_item::FEATURE => Tag::Feature(data.into()),
+            main_item::COLLECTION => {
+                let code = u8::try_from(data).map_err(|_| invalid)?;
+                Tag::Collection(CollectionType::try_from(code).map_err(|_| invalid)?)
+            },
+            main_item::END_COLLECTION => Tag::EndCollection,
 
-impl FromIterator<Tag> for ShortItems {
-    /// Construct a ShortItems sequence from an iterator of Tags.
-    fn from_iter<I: IntoIterator<Item = Tag>>(iter: I) -> Self {
-        Self(iter.into_iter().map(ShortItem::from).collect())
+            // Global tags
+            global_item::USAGE_PAGE =>
+                Tag::UsagePage(u16::try_from(data).map_err(|_| invalid)?),
+            global_item::LOGICAL_MINIMUM => Tag::LogicalMinimum(item.signed_data()),
+            global_item::LOGICAL_MAXIMUM => Tag::LogicalMaximum(item.signed_data()),
+            global_item::PHYSICAL_MINIMUM => Tag::PhysicalMinimum(item.signed_data()),
+            global_item::PHYSICAL_MAXIMUM => Tag::PhysicalMaximum(item.signed_data()),
+            global_item::UNIT_EXPONENT =>
+                Tag::UnitExponent(UnitExponent::try_from_data(item.signed_data()).map_err(|_| invalid)?),
+            global_item::UNIT => Tag::Unit(data.into()),
+            global_item::REPORT_SIZE => Tag::ReportSize(data),
+            global_item::REPORT_ID =>
+                Tag::ReportId(u8::try_from(data).map_err(|_| invalid)?),
+            global_item::REPORT_COUNT => Tag::ReportCount(data),
+            global_item::PUSH => Tag::Push,
+            global_item::POP => Tag::Pop,
+
+            // Local tags
+            // Usages with 4 bytes of data contain their own usage page.
+            local_item::USAGE if is_extended =>
+                Tag::ExtendedUsage(ExtendedUsage::new(data)),
+            local_item::USAGE => Tag::UsageId(data as u16),
+            local_item::USAGE_MINIMUM if is_extended =>
+                Tag::ExtendedUsageMinimum(ExtendedUsage::new(data)),
+            local_item::USAGE_MINIMUM => Tag::UsageMinimumId(data as u16),
+            local_item::USAGE_MAXIMUM if is_extended =>
+                Tag::ExtendedUsageMaximum(ExtendedUsage::new(data)),
+            local_item::USAGE_MAXIMUM => Tag::UsageMaximumId(data as u16),
+            local_item::DESIGNATOR_INDEX => Tag::DesignatorIndex(data.into()),
+            local_item::DESIGNATOR_MINIMUM => Tag::DesignatorMinimum(data.into()),
+            local_item::DESIGNATOR_MAXIMUM => Tag::DesignatorMaximum(data.into()),
+            local_item::STRING_INDEX => Tag::StringIndex(data.into()),
+            local_item::STRING_MINIMUM => Tag::StringMinimum(data.into()),
+            local_item::STRING_MAXIMUM => Tag::StringMaximum(data.into()),
+            local_item::DELIMITER => match data {
+                0 => Tag::Delimiter(Delimiter::close()),
+                1 => Tag::Delimiter(Delimiter::open()),
+                _ => return Err(invalid),
+            },
+
+            _ => return Err(ItemError::UnknownTag(item.size_type_tag().as_u8())),
+        };
+        Ok(tag)
     }
 }
 
+/// A sequence of ShortItems
+#[derive(Clone, Debug, Default)]
+pub struct ShortItems(Vec<ShortItem>);
+
 impl FromIterator<ShortItem> for ShortItems {
     /// Construct a ShortItems sequence from an iterator of ShortItems.
     fn from_iter<I: IntoIterator<Item = ShortItem>>(iter: I) -> Self {
@@ -425,6 +654,43 @@ impl FromIterator<ShortItem> for ShortItems {
 
 
 impl ShortItems {
+    /// Split a report descriptor into its ShortItems.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut items = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let item = ShortItem::from_bytes(&bytes[offset..])
+                .map_err(|error| DecodeError::new(offset, error))?;
+            offset += item.encoded_len();
+            items.push(item);
+        }
+        Ok(Self(items))
+    }
+
+    /// Construct a ShortItems sequence from Tags. Fails if any Tag holds a long item, which only
+    /// an Items sequence can carry.
+    pub fn try_from_tags<I: IntoIterator<Item = Tag>>(tags: I) -> Result<Self, ItemError> {
+        tags.into_iter().map(ShortItem::try_from).collect()
+    }
+
+    /// Returns an iterator over the contained ShortItems.
+    pub fn iter(&self) -> Iter<'_, ShortItem> {
+        self.0.iter()
+    }
+
+    /// Interpret each ShortItem as a Tag.
+    /// On failure, the returned error contains the byte offset of the offending item.
+    pub fn into_tags(self) -> Result<Box<[Tag]>, DecodeError> {
+        let mut offset = 0;
+        self.0.into_iter()
+            .map(|item| {
+                let item_offset = offset;
+                offset += item.encoded_len();
+                Tag::try_from(item).map_err(|error| DecodeError::new(item_offset, error))
+            })
+            .collect()
+    }
+
     pub fn into_bytes(self) -> Box<[u8]> {
         self.0.into_iter()
             .flat_map(ShortItem::into_bytes)
@@ -432,5 +698,152 @@ impl ShortItems {
     }
 }
 
+impl TryFrom<Item> for Tag {
+    type Error = ItemError;
+
+    /// Interpret an Item as a Tag. Long items are kept as they are.
+    fn try_from(item: Item) -> Result<Self, ItemError> {
+        match item {
+            Item::Short(short_item) => Tag::try_from(short_item),
+            Item::Long(long_item) => Ok(Tag::LongItem(long_item)),
+        }
+    }
+}
+
+/// A sequence of short and long Items
+#[derive(Clone, Debug, Default)]
+pub struct Items(Vec<Item>);
+
+impl FromIterator<Tag> for Items {
+    /// Construct an Items sequence from an iterator of Tags.
+    fn from_iter<I: IntoIterator<Item = Tag>>(iter: I) -> Self {
+        Self(iter.into_iter().map(Item::from).collect())
+    }
+}
+
+impl FromIterator<Item> for Items {
+    /// Construct an Items sequence from an iterator of Items.
+    fn from_iter<I: IntoIterator<Item = Item>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Items {
+    /// Split a report descriptor into its Items.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut items = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let item = Item::from_bytes(&bytes[offset..])
+                .map_err(|error| DecodeError::new(offset, error))?;
+            offset += item.encoded_len();
+            items.push(item);
+        }
+        Ok(Self(items))
+    }
+
+    /// Returns an iterator over the contained Items.
+    pub fn iter(&self) -> Iter<'_, Item> {
+        self.0.iter()
+    }
+
+    /// Interpret each Item as a Tag.
+    /// On failure, the returned error contains the byte offset of the offending item.
+    pub fn into_tags(self) -> Result<Box<[Tag]>, DecodeError> {
+        let mut offset = 0;
+        self.0.into_iter()
+            .map(|item| {
+                let item_offset = offset;
+                offset += item.encoded_len();
+                Tag::try_from(item).map_err(|error| DecodeError::new(item_offset, error))
+            })
+            .collect()
+    }
+
+    pub fn into_bytes(self) -> Box<[u8]> {
+        self.0.into_iter()
+            .flat_map(Item::into_bytes)
+            .collect()
+    }
+}
+
+impl IntoIterator for Items {
+    type Item = Item;
+    type IntoIter = <Vec<Item> as IntoIterator>::IntoIter;
 
+    /// Create an iterator through all items of this sequence.
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl IntoIterator for ShortItems {
+    type Item = ShortItem;
+    type IntoIter = <Vec<ShortItem> as IntoIterator>::IntoIter;
 
+    /// Create an iterator through all items of this sequence.
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::ReportFlags;
+
+    /// Usage Page (Generic Desktop), Usage (Mouse), a vendor long item, Input (Data,Var,Abs)
+    const BYTES: [u8; 11] = [0x05, 0x01, 0x09, 0x02, 0xFE, 0x02, 0x10, 0xAA, 0xBB, 0x81, 0x02];
+
+    #[test]
+    fn decodes_short_and_long_items() {
+        let tags = Items::from_bytes(&BYTES).unwrap().into_tags().unwrap();
+        assert_eq!(&*tags, &[
+            Tag::UsagePage(0x01),
+            Tag::UsageId(0x02),
+            Tag::LongItem(LongItem::new(0x10, &[0xAA, 0xBB]).unwrap()),
+            Tag::Input(ReportFlags::from(0x02)),
+        ]);
+        assert_eq!(&*Items::from_iter(tags.into_vec()).into_bytes(), &BYTES);
+    }
+
+    #[test]
+    fn short_items_reject_long_items() {
+        let error = ShortItems::from_bytes(&BYTES).unwrap_err();
+        assert_eq!(error, DecodeError::new(4, ItemError::UnknownTag(LONG_ITEM_PREFIX)));
+
+        let tags = Items::from_bytes(&BYTES).unwrap().into_tags().unwrap();
+        assert!(ShortItems::try_from_tags(tags.iter().cloned()).is_err());
+        let short_tags = tags.into_vec().into_iter().filter(|tag| !matches!(tag, Tag::LongItem(_)));
+        let short_items = ShortItems::try_from_tags(short_tags).unwrap();
+        assert_eq!(&*short_items.into_bytes(), &[0x05, 0x01, 0x09, 0x02, 0x81, 0x02]);
+    }
+
+    #[test]
+    fn reports_offset_of_truncated_items() {
+        let error = Items::from_bytes(&[0x05, 0x01, 0x26, 0xFF]).unwrap_err();
+        assert_eq!(error, DecodeError::new(2, ItemError::Truncated));
+        let error = Items::from_bytes(&[0xFE, 0x04, 0x10, 0x00]).unwrap_err();
+        assert_eq!(error, DecodeError::new(0, ItemError::Truncated));
+    }
+
+    #[test]
+    fn decodes_short_items_into_tags() {
+        // Usage Page (Generic Desktop), Logical Minimum (-127), Input (Data,Var,Abs)
+        let bytes = [0x05, 0x01, 0x15, 0x81, 0x81, 0x02];
+        let items = ShortItems::from_bytes(&bytes).unwrap();
+        assert_eq!(items.iter().map(ShortItem::signed_data).collect::<Vec<_>>(), [1, -127, 2]);
+        assert_eq!(&*items.clone().into_bytes(), &bytes);
+        assert_eq!(&*items.into_tags().unwrap(), &[
+            Tag::UsagePage(0x01),
+            Tag::LogicalMinimum(-127),
+            Tag::Input(ReportFlags::from(0x02)),
+        ]);
+
+        let error = ShortItems::from_bytes(&[0x05, 0x01, 0x26, 0xFF]).unwrap_err();
+        assert_eq!(error, DecodeError::new(2, ItemError::Truncated));
+    }
+}
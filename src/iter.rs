@@ -1,13 +1,15 @@
-/// Iterables for reports
+//! Iterables for reports
 
 use std::iter::FlatMap;
 use std::slice::Iter;
 
 use super::collection::{Collection, CollectionItem};
-use super::error::MissingIdError;
+use super::descriptor::ReportDescriptor;
+use super::error::ReportIdError;
 use super::field_types::ReportId;
-use super::format::{ReportFormat, ReportItem, TooLargeError};
-use super::report::Report;
+use super::format::{LayoutError, ReportFormat, ReportItem};
+use super::layout::{LayoutTable, ReportLayout};
+use super::report::{Report, ReportType, ReportTypes};
 
 /// Helper function for folding an iterator into distinct items, preserving order.
 fn fold_unique<T: PartialEq>(mut vec: Vec<T>, item: T) -> Vec<T> {
@@ -56,6 +58,7 @@ impl <'a> Iterator for ReportIter<'a> {
                     // Restart loop.
                     // Normally, this would be handled by tail recursion.
                 },
+                Some(CollectionItem::LongItem(_)) => (),
                 None => {
                     return None;
                 },
@@ -72,59 +75,145 @@ pub trait ToReportIterator<'a>: Sized {
     /// Returns an iterator over all reports in this Collection.
     fn to_report_iter(self) -> Self::ReportIter;
 
-    /// Create an unfilled ReportFormat with this Collection's input reports.
-    fn input_report_format(self, report_id: Option<ReportId>) -> Result<ReportFormat, TooLargeError> {
+    /// Create an unfilled ReportFormat with this Collection's input reports, padded to a byte boundary
+    /// as by Collection::into_bytes.
+    fn input_report_format(self, report_id: Option<ReportId>) -> Result<ReportFormat, LayoutError> {
         let report_items = self.to_report_iter()
             .filter(|report| report.is_input())
             .filter(|report| report.report_id == report_id)
-            .flat_map(|report|
-                      [ReportItem::from_report(report)].repeat(report.report_count as usize));
+            .flat_map(ReportItem::from_report_fields);
 
-        ReportFormat::new_with_opt_id(report_id).copy_from_iter(report_items)
+        let mut format = ReportFormat::new_with_opt_id(report_id).copy_from_iter(report_items)?;
+        format.pad_to_byte_boundary()?;
+        Ok(format)
     }
     
-    /// Create an unfilled ReportFormat with this Collection's output reports.
-    fn output_report_format(self, report_id: Option<ReportId>) -> Result<ReportFormat, TooLargeError> {
+    /// Create an unfilled ReportFormat with this Collection's output reports, padded to a byte boundary
+    /// as by Collection::into_bytes.
+    fn output_report_format(self, report_id: Option<ReportId>) -> Result<ReportFormat, LayoutError> {
         let report_items = self.to_report_iter()
             .filter(|report| report.is_output())
             .filter(|report| report.report_id == report_id)
-            .flat_map(|report|
-                      [ReportItem::from_report(report)].repeat(report.report_count as usize));
+            .flat_map(ReportItem::from_report_fields);
 
-        ReportFormat::new_with_opt_id(report_id).copy_from_iter(report_items)
+        let mut format = ReportFormat::new_with_opt_id(report_id).copy_from_iter(report_items)?;
+        format.pad_to_byte_boundary()?;
+        Ok(format)
     }
     
-    /// Create an unfilled ReportFormat with this Collection's feature reports.
-    fn feature_report_format(self, report_id: Option<ReportId>) -> Result<ReportFormat, TooLargeError> {
+    /// Create an unfilled ReportFormat with this Collection's feature reports, padded to a byte boundary
+    /// as by Collection::into_bytes.
+    fn feature_report_format(self, report_id: Option<ReportId>) -> Result<ReportFormat, LayoutError> {
         let report_items = self.to_report_iter()
             .filter(|report| report.is_feature())
             .filter(|report| report.report_id == report_id)
-            .flat_map(|report|
-                      [ReportItem::from_report(report)].repeat(report.report_count as usize));
+            .flat_map(ReportItem::from_report_fields);
 
-        ReportFormat::new_with_opt_id(report_id).copy_from_iter(report_items)
+        let mut format = ReportFormat::new_with_opt_id(report_id).copy_from_iter(report_items)?;
+        format.pad_to_byte_boundary()?;
+        Ok(format)
     }
 
-    /// Collect all IDs contained.
-    /// Returns MissingIdError if some but not all reports have IDs.
-    fn input_ids(self) -> Result<Box<[ReportId]>, MissingIdError> {
-        let report_id_opts = self.to_report_iter()
-            .filter(|report| report.is_input())
-            .map(|report| report.report_id)
+    /// Compute the layout of every report type and ID, with the longest report of each type.
+    fn layout(self) -> Result<LayoutTable, LayoutError> where Self: Copy {
+        let keys = self.to_report_iter()
+            .map(|report| (report.report_type(), report.report_id))
             .fold(Vec::new(), fold_unique);
-        
-        if report_id_opts.len() == 1 && report_id_opts[0] == None {
-            Ok(Box::new([]))
-        } else if report_id_opts.iter().all(Option::is_some) {
-            Ok(report_id_opts.into_iter()
-               .map(Option::unwrap)
-               .collect())
-        } else {
-            Err(MissingIdError {})
+
+        let reports = keys.into_iter()
+            .map(|(report_type, report_id)| {
+                let format = match report_type {
+                    ReportType::Input => self.input_report_format(report_id)?,
+                    ReportType::Output => self.output_report_format(report_id)?,
+                    ReportType::Feature => self.feature_report_format(report_id)?,
+                };
+                Ok(ReportLayout::from_format(report_type, &format))
+            })
+            .collect::<Result<Vec<_>, LayoutError>>()?;
+        Ok(LayoutTable::new(reports))
+    }
+
+    /// Collect all IDs of input reports, in order of first use.
+    /// Returns ReportIdError if some but not all input reports have IDs, or if an ID is reserved.
+    fn input_ids(self) -> Result<Box<[ReportId]>, ReportIdError> {
+        collect_ids(self.to_report_iter().filter(|report| report.is_input()))
+    }
+
+    /// Collect all IDs of output reports, in order of first use.
+    /// Returns ReportIdError if some but not all output reports have IDs, or if an ID is reserved.
+    fn output_ids(self) -> Result<Box<[ReportId]>, ReportIdError> {
+        collect_ids(self.to_report_iter().filter(|report| report.is_output()))
+    }
+
+    /// Collect all IDs of feature reports, in order of first use.
+    /// Returns ReportIdError if some but not all feature reports have IDs, or if an ID is
+    /// reserved.
+    fn feature_ids(self) -> Result<Box<[ReportId]>, ReportIdError> {
+        collect_ids(self.to_report_iter().filter(|report| report.is_feature()))
+    }
+
+    /// Collect all IDs, in order of first use, with the set of report types using each ID.
+    /// Returns ReportIdError if some but not all reports of any type have IDs, or if an ID is
+    /// reserved.
+    fn report_ids(self) -> Result<Box<[(ReportId, ReportTypes)]>, ReportIdError> {
+        let mut report_ids: Vec<(ReportId, ReportTypes)> = Vec::new();
+        let mut id_check = ReportIdCheck::default();
+        for report in self.to_report_iter() {
+            id_check.check(report)?;
+            if let Some(report_id) = report.report_id {
+                match report_ids.iter_mut().find(|(id, _)| *id == report_id) {
+                    Some((_, report_types)) => report_types.insert(report.report_type()),
+                    None => report_ids.push((report_id, ReportTypes::default().with(report.report_type()))),
+                }
+            }
+        }
+        Ok(report_ids.into_boxed_slice())
+    }
+}
+
+/// Checks that either all reports or no reports have IDs, and that no report uses the reserved
+/// ID 0, one report at a time.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ReportIdCheck {
+    /// The first report without an ID, and the first report ID.
+    missing_type: Option<ReportType>,
+    report_id: Option<ReportId>,
+}
+
+impl ReportIdCheck {
+    /// Check the next report. Returns ReportIdError for the first report whose ID is reserved, or
+    /// which has an ID while an earlier report has none, or the reverse.
+    pub(crate) fn check(&mut self, report: &Report) -> Result<(), ReportIdError> {
+        match report.report_id {
+            Some(0) => return Err(ReportIdError::OutOfRange(0)),
+            Some(report_id) => {
+                self.report_id.get_or_insert(report_id);
+            },
+            None => {
+                self.missing_type.get_or_insert(report.report_type());
+            },
+        }
+        match (self.missing_type, self.report_id) {
+            (Some(report_type), Some(report_id)) => Err(ReportIdError::MissingId { report_type, report_id }),
+            _ => Ok(()),
         }
     }
 }
 
+/// Collect the distinct IDs of reports, in order of first use, checking that either all reports
+/// or no reports have IDs.
+fn collect_ids<'a, I: Iterator<Item = &'a Report>>(reports: I) -> Result<Box<[ReportId]>, ReportIdError> {
+    let mut report_ids = Vec::new();
+    let mut id_check = ReportIdCheck::default();
+    for report in reports {
+        id_check.check(report)?;
+        if let Some(report_id) = report.report_id {
+            report_ids = fold_unique(report_ids, report_id);
+        }
+    }
+    Ok(report_ids.into_boxed_slice())
+}
+
 impl<'a> ToReportIterator<'a> for &'a Collection {
     type ReportIter = ReportIter<'a>;
     /// Returns an iterator over all reports in this Collection.
@@ -145,3 +234,69 @@ impl<'a> ToReportIterator<'a> for &'a [Collection] {
     }
 }
 
+impl<'a> ToReportIterator<'a> for &'a ReportDescriptor {
+    type ReportIter = <&'a [Collection] as ToReportIterator<'a>>::ReportIter;
+
+    /// Returns an iterator over all reports in every top-level collection, in order.
+    fn to_report_iter(self) -> Self::ReportIter {
+        self.collections().to_report_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::{CollectionType, ReportFlags};
+    use super::super::report::ReportMain;
+    use super::super::usage::{Usage, UsageSet};
+
+    fn report(report_type: ReportType, report_id: Option<ReportId>) -> Report {
+        let usage_set = UsageSet::empty().with_usage(Usage::new(0x01, 0x30));
+        let report = Report::new_input(ReportFlags::new().as_variable(), usage_set, 0, 255, 8, 1);
+        let report = Report { main: ReportMain::new(report_type, report.main.report_flags), ..report };
+        match report_id {
+            Some(report_id) => report.with_report_id(report_id),
+            None => report,
+        }
+    }
+
+    fn application(reports: Vec<Report>) -> Collection {
+        let items: Box<[CollectionItem]> = reports.into_iter().map(Into::into).collect();
+        Collection::new(CollectionType::Application, Usage::new(0x01, 0x02), items)
+    }
+
+    #[test]
+    fn collects_ids_by_type_in_order_of_first_use() {
+        let nested = Collection::new(CollectionType::Physical, Usage::new(0x01, 0x01),
+            (report(ReportType::Feature, Some(2)),));
+        let collection = Collection::new(CollectionType::Application, Usage::new(0x01, 0x02), (
+            report(ReportType::Input, Some(3)),
+            report(ReportType::Output, Some(2)),
+            nested,
+            report(ReportType::Input, Some(1)),
+            report(ReportType::Input, Some(3)),
+        ));
+        assert_eq!(collection.input_ids(), Ok(Box::from([3, 1])));
+        assert_eq!(collection.output_ids(), Ok(Box::from([2])));
+        assert_eq!(collection.feature_ids(), Ok(Box::from([2])));
+        let output_and_feature = ReportTypes::default().with(ReportType::Output).with(ReportType::Feature);
+        assert_eq!(collection.report_ids(), Ok(Box::from([
+            (3, ReportTypes::default().with(ReportType::Input)),
+            (2, output_and_feature),
+            (1, ReportTypes::default().with(ReportType::Input)),
+        ])));
+    }
+
+    #[test]
+    fn rejects_missing_and_reserved_ids() {
+        let mixed = application(vec![report(ReportType::Output, None), report(ReportType::Input, Some(4))]);
+        let error = ReportIdError::MissingId { report_type: ReportType::Output, report_id: 4 };
+        assert_eq!(mixed.report_ids(), Err(error));
+        // Each type is checked on its own.
+        assert_eq!(mixed.input_ids(), Ok(Box::from([4])));
+        assert_eq!(mixed.output_ids(), Ok(Box::from([])));
+
+        let reserved = application(vec![report(ReportType::Input, Some(0))]);
+        assert_eq!(reserved.input_ids(), Err(ReportIdError::OutOfRange(0)));
+    }
+}
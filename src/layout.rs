@@ -0,0 +1,174 @@
+//! Layout of reports in bytes, similar to the capabilities reported by the Windows HID parser.
+
+use super::field_types::ReportId;
+use super::format::{ReportFormat, ReportVariable};
+use super::report::ReportType;
+use super::usage::Usage;
+
+/// The kind of data held by a field.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FieldKind {
+    /// A single value.
+    Variable,
+    /// A slot of an array, holding the index of an active usage.
+    ArraySlot,
+    /// A field of buffered bytes.
+    Buffer,
+    /// Constant bits, such as padding.
+    Constant,
+}
+
+/// The position of a single field in a report.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FieldLayout {
+    pub kind: FieldKind,
+    /// Offset of the field from the start of the report, in bits, including the ID prefix.
+    pub bit_offset: u64,
+    /// Width of the field, in bits.
+    pub bit_size: u32,
+    /// Usage of the field. Array slots and constants have no usage.
+    pub usage: Option<Usage>,
+}
+
+/// The layout of the report of a single type and ID.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReportLayout {
+    pub report_type: ReportType,
+    pub report_id: Option<ReportId>,
+    /// Length of the report, in bits, including the ID prefix and padding.
+    pub bit_len: u64,
+    /// Length of the report, in bytes, including the ID prefix.
+    pub byte_len: usize,
+    /// Fields of the report, in order of their offsets.
+    pub fields: Box<[FieldLayout]>,
+}
+
+impl ReportLayout {
+    /// Construct the layout of a ReportFormat of the given type.
+    pub fn from_format(report_type: ReportType, format: &ReportFormat) -> Self {
+        let id_size: u64 = if format.report_id().is_some() { 8 } else { 0 };
+        let field = |kind, variable: &ReportVariable| FieldLayout {
+            kind,
+            bit_offset: id_size + u64::from(variable.bit_offset()),
+            bit_size: variable.bit_size(),
+            usage: match kind {
+                FieldKind::Variable | FieldKind::Buffer => variable.usage(),
+                FieldKind::ArraySlot | FieldKind::Constant => None,
+            },
+        };
+
+        let mut fields: Vec<FieldLayout> = format.iter()
+            .map(|variable| match variable.is_buffered() {
+                true => field(FieldKind::Buffer, variable),
+                false => field(FieldKind::Variable, variable),
+            })
+            .chain(format.arrays()
+                   .flat_map(|array| array.slots().iter())
+                   .map(|slot| field(FieldKind::ArraySlot, slot)))
+            .chain(format.constants().map(|constant| field(FieldKind::Constant, constant)))
+            .collect();
+        fields.sort_by_key(|field| field.bit_offset);
+
+        Self {
+            report_type,
+            report_id: format.report_id(),
+            bit_len: format.bit_len(),
+            byte_len: format.byte_len(),
+            fields: fields.into_boxed_slice(),
+        }
+    }
+}
+
+/// The layout of every report of a descriptor.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LayoutTable {
+    /// Layout of each report type and ID, in order of type and ID.
+    pub reports: Box<[ReportLayout]>,
+    /// Length of the longest input report, in bytes, including the ID prefix.
+    ///
+    /// Unlike InputReportByteLength of HIDP_CAPS, no byte is counted for the ID when reports have
+    /// no IDs.
+    pub input_report_byte_length: usize,
+    /// Length of the longest output report, in bytes, including the ID prefix.
+    pub output_report_byte_length: usize,
+    /// Length of the longest feature report, in bytes, including the ID prefix.
+    pub feature_report_byte_length: usize,
+}
+
+impl LayoutTable {
+    /// Construct a LayoutTable from the layout of each report.
+    pub fn new(mut reports: Vec<ReportLayout>) -> Self {
+        reports.sort_by_key(|report| (report.report_type, report.report_id));
+        let max_len = |report_type| reports.iter()
+            .filter(|report| report.report_type == report_type)
+            .map(|report| report.byte_len)
+            .max()
+            .unwrap_or(0);
+        Self {
+            input_report_byte_length: max_len(ReportType::Input),
+            output_report_byte_length: max_len(ReportType::Output),
+            feature_report_byte_length: max_len(ReportType::Feature),
+            reports: reports.into_boxed_slice(),
+        }
+    }
+
+    /// Returns the layout of the report with the given type and ID.
+    pub fn report(&self, report_type: ReportType, report_id: Option<ReportId>) -> Option<&ReportLayout> {
+        self.reports.iter()
+            .find(|report| report.report_type == report_type && report.report_id == report_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::collection::Collection;
+    use super::super::field_types::{CollectionType, ReportFlags};
+    use super::super::iter::ToReportIterator;
+    use super::super::report::Report;
+    use super::super::usage::UsageSet;
+
+    fn buttons() -> Report {
+        let usage_set = UsageSet::empty().with_usage_bounds(Usage::new(0x09, 1), Usage::new(0x09, 3));
+        Report::new_input(ReportFlags::new().as_variable(), usage_set, 0, 1, 1, 3)
+    }
+
+    fn keys() -> Report {
+        let usage_set = UsageSet::empty().with_usage_bounds(Usage::new(0x07, 0x04), Usage::new(0x07, 0x1D));
+        Report::new_input(ReportFlags::new().as_array(), usage_set, 0, 25, 8, 2)
+    }
+
+    #[test]
+    fn lays_out_fields_after_the_report_id() {
+        let collection = Collection::new(CollectionType::Application, Usage::new(0x01, 0x06),
+            (buttons().with_report_id(2), keys().with_report_id(2)));
+        let table = collection.layout().unwrap();
+        let report = table.report(ReportType::Input, Some(2)).unwrap();
+
+        // 8 bits of ID, 3 buttons, 2 key slots and 5 bits of padding at the end.
+        assert_eq!((report.bit_len, report.byte_len), (32, 4));
+        let kinds: Vec<_> = report.fields.iter().map(|field| (field.kind, field.bit_offset, field.bit_size)).collect();
+        assert_eq!(kinds, [
+            (FieldKind::Variable, 8, 1),
+            (FieldKind::Variable, 9, 1),
+            (FieldKind::Variable, 10, 1),
+            (FieldKind::ArraySlot, 11, 8),
+            (FieldKind::ArraySlot, 19, 8),
+            (FieldKind::Constant, 27, 5),
+        ]);
+        assert_eq!(report.fields[2].usage, Some(Usage::new(0x09, 3)));
+        assert_eq!(report.fields[3].usage, None);
+    }
+
+    #[test]
+    fn finds_the_longest_report_of_each_type() {
+        let collection = Collection::new(CollectionType::Application, Usage::new(0x01, 0x06),
+            (buttons().with_report_id(1), keys().with_report_id(2)));
+        let table = collection.layout().unwrap();
+        assert_eq!(table.reports.len(), 2);
+        assert_eq!(table.report(ReportType::Input, Some(1)).map(|report| report.byte_len), Some(2));
+        assert_eq!(table.input_report_byte_length, 3);
+        assert_eq!((table.output_report_byte_length, table.feature_report_byte_length), (0, 0));
+        assert_eq!(table.report(ReportType::Output, Some(1)), None);
+    }
+}
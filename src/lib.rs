@@ -1,13 +1,25 @@
 pub mod collection;
+pub mod decompiler;
+pub mod descriptor;
 pub mod error;
+pub mod export;
+pub mod import;
 pub mod format;
 pub mod into_bytes;
 pub mod item;
+pub mod layout;
+pub mod listing;
 pub mod iter;
+pub mod merge;
 pub mod field_types;
 pub mod optimizer;
+pub mod padding;
+pub mod physical;
 pub mod report;
 pub mod tag;
+pub mod text;
+pub mod unit;
 pub mod usage;
 pub mod usage_tables;
+pub mod validate;
 
@@ -0,0 +1,267 @@
+//! Human-readable listings of report descriptors, in the style of the HID Descriptor Tool.
+
+use std::fmt::{self, Display, Formatter};
+
+use super::collection::Collection;
+use super::descriptor::ReportDescriptor;
+use super::error::DecodeError;
+use super::field_types::UnitExponentEncoding;
+use super::item::Items;
+use super::tag::Tag;
+use super::usage::{ExtendedUsage, Usage, UsageId, UsagePage};
+use super::usage_tables::{page_name, usage_name};
+
+/// Number of spaces added for each level of collection.
+const INDENT_WIDTH: usize = 4;
+
+/// Format a usage page by name, or in hexadecimal if it has no name.
+fn format_usage_page(usage_page: UsagePage) -> String {
+    match page_name(usage_page) {
+        Some(name) => name.to_string(),
+        None => format!("0x{:04X}", usage_page),
+    }
+}
+
+/// Format a usage ID by name within the current usage page, or in hexadecimal if it has no name.
+fn format_usage_id(usage_page: Option<UsagePage>, usage_id: UsageId) -> String {
+    match usage_page.and_then(|usage_page| usage_name(Usage::new(usage_page, usage_id))) {
+        Some(name) => name.into_owned(),
+        None => format!("0x{:04X}", usage_id),
+    }
+}
+
+/// Format an extended usage with both its page and its ID, such as "Generic Desktop: Mouse".
+fn format_extended_usage(usage: ExtendedUsage) -> String {
+    match (page_name(usage.page()), usage_name(Usage::from_extended(usage))) {
+        (Some(page), Some(name)) => format!("{}: {}", page, name),
+        _ => format!("0x{:08X}", usage.as_u32()),
+    }
+}
+
+/// One line of a listing: the description of a tag, and its depth within collections.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TagLine {
+    pub depth: usize,
+    pub text: String,
+}
+
+impl Display for TagLine {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{:indent$}{}", "", self.text, indent = self.depth * INDENT_WIDTH)
+    }
+}
+
+/// Describes a sequence of tags, in order.
+///
+/// Usage IDs are named within the current usage page, so the usage page is tracked through Push
+/// and Pop tags, as are the collections which set the depth of each line.
+#[derive(Clone, Debug, Default)]
+pub struct TagDescriber {
+    usage_page: Option<UsagePage>,
+    saved_usage_pages: Vec<Option<UsagePage>>,
+    depth: usize,
+}
+
+impl TagDescriber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describe the next tag, such as "Usage (Mouse)" or "Input (Data,Var,Abs)".
+    pub fn describe(&mut self, tag: &Tag) -> TagLine {
+        if let Tag::EndCollection = tag {
+            self.depth = self.depth.saturating_sub(1);
+        }
+        let depth = self.depth;
+        let text = match tag {
+            Tag::Input(flags) => format!("Input ({})", flags),
+            Tag::Output(flags) => format!("Output ({})", flags),
+            Tag::Feature(flags) => format!("Feature ({})", flags),
+            Tag::Collection(collection_type) => {
+                self.depth += 1;
+                format!("Collection ({})", collection_type)
+            },
+            Tag::EndCollection => "End Collection".to_string(),
+
+            Tag::UsagePage(usage_page) => {
+                self.usage_page = Some(*usage_page);
+                format!("Usage Page ({})", format_usage_page(*usage_page))
+            },
+            Tag::LogicalMinimum(value) => format!("Logical Minimum ({})", value),
+            Tag::LogicalMaximum(value) => format!("Logical Maximum ({})", value),
+            Tag::PhysicalMinimum(value) => format!("Physical Minimum ({})", value),
+            Tag::PhysicalMaximum(value) => format!("Physical Maximum ({})", value),
+            // Negative exponents written as a full byte are marked, so that they are parsed back
+            // into the same bytes.
+            Tag::UnitExponent(unit_exponent) => match unit_exponent.encoding() {
+                UnitExponentEncoding::SignedByte if unit_exponent.as_i8() < 0 =>
+                    format!("Unit Exponent ({}, byte)", unit_exponent.as_i8()),
+                _ => format!("Unit Exponent ({})", unit_exponent.as_i8()),
+            },
+            Tag::Unit(unit) => format!("Unit ({})", unit),
+            Tag::ReportSize(value) => format!("Report Size ({})", value),
+            Tag::ReportId(value) => format!("Report ID ({})", value),
+            Tag::ReportCount(value) => format!("Report Count ({})", value),
+            Tag::Push => {
+                self.saved_usage_pages.push(self.usage_page);
+                "Push".to_string()
+            },
+            Tag::Pop => {
+                if let Some(usage_page) = self.saved_usage_pages.pop() {
+                    self.usage_page = usage_page;
+                }
+                "Pop".to_string()
+            },
+
+            Tag::ExtendedUsage(usage) => format!("Usage ({})", format_extended_usage(*usage)),
+            Tag::UsageId(usage_id) => format!("Usage ({})", format_usage_id(self.usage_page, *usage_id)),
+            Tag::ExtendedUsageMinimum(usage) =>
+                format!("Usage Minimum ({})", format_extended_usage(*usage)),
+            Tag::UsageMinimumId(usage_id) =>
+                format!("Usage Minimum ({})", format_usage_id(self.usage_page, *usage_id)),
+            Tag::ExtendedUsageMaximum(usage) =>
+                format!("Usage Maximum ({})", format_extended_usage(*usage)),
+            Tag::UsageMaximumId(usage_id) =>
+                format!("Usage Maximum ({})", format_usage_id(self.usage_page, *usage_id)),
+            Tag::DesignatorIndex(index) => format!("Designator Index ({})", u32::from(*index)),
+            Tag::DesignatorMinimum(index) => format!("Designator Minimum ({})", u32::from(*index)),
+            Tag::DesignatorMaximum(index) => format!("Designator Maximum ({})", u32::from(*index)),
+            Tag::StringIndex(index) => format!("String Index ({})", u32::from(*index)),
+            Tag::StringMinimum(index) => format!("String Minimum ({})", u32::from(*index)),
+            Tag::StringMaximum(index) => format!("String Maximum ({})", u32::from(*index)),
+            Tag::Delimiter(delimiter) if delimiter.is_open() => "Delimiter (Open)".to_string(),
+            Tag::Delimiter(_) => "Delimiter (Close)".to_string(),
+
+            Tag::LongItem(long_item) => {
+                let data: String = long_item.data().iter().map(|byte| format!(", 0x{:02X}", byte)).collect();
+                format!("Long Item (0x{:02X}{})", long_item.tag(), data)
+            },
+        };
+        TagLine { depth, text }
+    }
+}
+
+/// An indented listing of the tags of a report descriptor, with one tag on each line.
+///
+/// Collections and descriptors are listed as compiled by into_bytes, so that a descriptor lists
+/// the same way before and after compiling.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Listing(Box<[Tag]>);
+
+impl Listing {
+    /// List the tags of a compiled report descriptor.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Ok(Self(Items::from_bytes(bytes)?.into_tags()?))
+    }
+
+    pub fn tags(&self) -> &[Tag] {
+        &self.0
+    }
+
+    pub fn into_tags(self) -> Box<[Tag]> {
+        self.0
+    }
+
+    /// Compile the listed tags into a report descriptor, without any optimization.
+    pub fn into_bytes(self) -> Box<[u8]> {
+        Items::from_iter(self.0.into_vec()).into_bytes()
+    }
+
+    /// Returns the description of each tag, in order.
+    pub fn lines(&self) -> Vec<TagLine> {
+        let mut describer = TagDescriber::new();
+        self.0.iter().map(|tag| describer.describe(tag)).collect()
+    }
+}
+
+impl FromIterator<Tag> for Listing {
+    fn from_iter<I: IntoIterator<Item = Tag>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl From<Collection> for Listing {
+    fn from(collection: Collection) -> Self {
+        Self(collection.into_tags())
+    }
+}
+
+impl From<ReportDescriptor> for Listing {
+    fn from(descriptor: ReportDescriptor) -> Self {
+        Self(descriptor.into_tags())
+    }
+}
+
+impl Display for Listing {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        for line in self.lines() {
+            writeln!(fmt, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Collection {
+    /// List this collection as compiled by Collection::into_bytes.
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        Listing::from(self.clone()).fmt(fmt)
+    }
+}
+
+impl Display for ReportDescriptor {
+    /// List this descriptor as compiled by ReportDescriptor::into_bytes.
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        Listing::from(self.clone()).fmt(fmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indents_collections_and_names_usages() {
+        let bytes = [
+            0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x05, 0x09, 0x19, 0x01, 0x29, 0x03,
+            0x95, 0x03, 0x75, 0x01, 0x81, 0x02, 0x09, 0x99, 0xC0,
+        ];
+        let listing = Listing::from_bytes(&bytes).unwrap();
+        assert_eq!(listing.to_string(), "\
+Usage Page (Generic Desktop)
+Usage (Mouse)
+Collection (Application)
+    Usage Page (Button)
+    Usage Minimum (Button 1)
+    Usage Maximum (Button 3)
+    Report Count (3)
+    Report Size (1)
+    Input (Data,Var,Abs)
+    Usage (Button 153)
+End Collection
+");
+    }
+
+    #[test]
+    fn tracks_the_usage_page_through_push_and_pop() {
+        let tags = [
+            Tag::UsagePage(0x01),
+            Tag::Push,
+            Tag::UsagePage(0xFF00),
+            Tag::UsageId(0x01),
+            Tag::Pop,
+            Tag::UsageId(0x30),
+            Tag::ExtendedUsage(ExtendedUsage::new(0x0009_0002)),
+        ];
+        let mut describer = TagDescriber::new();
+        let lines: Vec<String> = tags.iter().map(|tag| describer.describe(tag).text).collect();
+        assert_eq!(lines, [
+            "Usage Page (Generic Desktop)",
+            "Push",
+            "Usage Page (0xFF00)",
+            "Usage (0x0001)",
+            "Pop",
+            "Usage (X)",
+            "Usage (Button: Button 2)",
+        ]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Merging of adjacent reports which only differ by usage.
+
+use super::collection::{Collection, CollectionItem};
+use super::report::Report;
+
+/// Returns true if the fields of the second report can be appended to the first report without
+/// changing the layout or the usage of any field.
+fn can_merge(first: &Report, second: &Report) -> bool {
+    // Every item other than the usages and count must match.
+    let second_with_first_usages = Report {
+        usage_set: first.usage_set.clone(),
+        report_count: first.report_count,
+        ..second.clone()
+    };
+    if &second_with_first_usages != first {
+        return false;
+    }
+
+    let flags = first.main.report_flags;
+    if flags.is_constant() && first.usage_set.is_empty() && second.usage_set.is_empty() {
+        // Padding has no usages to keep.
+        return true;
+    }
+    // Usages of an array are a list of choices, which cannot be merged. In a variable item, each
+    // usage belongs to a single field, so the first report needs exactly one usage per field.
+    // Otherwise, the last usage of the first report would have been repeated, or its extra usages
+    // would be given to the second report's fields.
+    flags.is_variable()
+        && first.usage_set.len() == first.report_count
+        && !second.usage_set.is_empty()
+        && first.report_count.checked_add(second.report_count).is_some()
+}
+
+/// Merge a sequence of collection items, merging adjacent reports where possible.
+fn merge_items(items: Box<[CollectionItem]>) -> Box<[CollectionItem]> {
+    let mut merged_items: Vec<CollectionItem> = Vec::with_capacity(items.len());
+    for item in items.into_vec() {
+        match (merged_items.last_mut(), item) {
+            (Some(CollectionItem::Report(last)), CollectionItem::Report(mut report))
+                if can_merge(last, &report) => {
+                last.report_count += report.report_count;
+                last.usage_set.append(&mut report.usage_set);
+            },
+            (_, CollectionItem::Collection(collection)) => {
+                merged_items.push(collection.merge_reports().into());
+            },
+            (_, item) => merged_items.push(item),
+        }
+    }
+    merged_items.into_boxed_slice()
+}
+
+impl Collection {
+    /// Merge runs of adjacent reports which share all global and local items other than their
+    /// usages into single reports, as would be done in a hand-written descriptor. The layout of
+    /// each report, and the usage of each field, is unchanged.
+    pub fn merge_reports(mut self) -> Self {
+        self.items = merge_items(self.items);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::{CollectionType, ReportFlags};
+    use super::super::usage::{Usage, UsageSet};
+
+    fn variable(ids: &[u16]) -> Report {
+        let usage_set = ids.iter()
+            .fold(UsageSet::empty(), |usage_set, &id| usage_set.with_usage(Usage::new(0x01, id)));
+        Report::new_input(ReportFlags::new().as_variable(), usage_set, -127, 127, 8, ids.len() as u32)
+    }
+
+    fn merged(items: Vec<CollectionItem>) -> Box<[CollectionItem]> {
+        Collection::new(CollectionType::Application, Usage::new(0x01, 0x02), items.into_boxed_slice())
+            .merge_reports()
+            .items
+    }
+
+    #[test]
+    fn merges_variables_which_differ_by_usage() {
+        let items = merged(vec![variable(&[0x30]).into(), variable(&[0x31]).into(), variable(&[0x38]).into()]);
+        assert_eq!(&*items, &[variable(&[0x30, 0x31, 0x38]).into()]);
+    }
+
+    #[test]
+    fn keeps_reports_with_other_globals() {
+        let mut wide = variable(&[0x31]);
+        wide.report_size = 16;
+        let items = vec![variable(&[0x30]).into(), wide.into()];
+        assert_eq!(merged(items.clone()), items.into_boxed_slice());
+    }
+
+    #[test]
+    fn keeps_reports_whose_last_usage_repeats() {
+        // The second field of the first report uses 0x30 again, which would become 0x31 once merged.
+        let mut repeated = variable(&[0x30]);
+        repeated.report_count = 2;
+        let items = vec![repeated.into(), variable(&[0x31]).into()];
+        assert_eq!(merged(items.clone()), items.into_boxed_slice());
+    }
+
+    #[test]
+    fn keeps_arrays_and_merges_padding() {
+        let mut array = variable(&[0x30, 0x31]);
+        array.main.report_flags = ReportFlags::new().as_array();
+        array.report_count = 1;
+        let padding = Report::new_input(ReportFlags::new().as_constant(), UsageSet::empty(), 0, 0, 1, 3);
+        let items = merged(vec![array.clone().into(), array.clone().into(), padding.clone().into(), padding.into()]);
+        let merged_padding = Report::new_input(ReportFlags::new().as_constant(), UsageSet::empty(), 0, 0, 1, 6);
+        assert_eq!(&*items, &[array.clone().into(), array.into(), merged_padding.into()]);
+    }
+
+    #[test]
+    fn merges_within_nested_collections() {
+        let physical = Collection::new(CollectionType::Physical, Usage::new(0x01, 0x01),
+            (variable(&[0x30]), variable(&[0x31])));
+        let items = merged(vec![physical.into()]);
+        match &*items {
+            [CollectionItem::Collection(physical)] =>
+                assert_eq!(physical.items(), &[variable(&[0x30, 0x31]).into()]),
+            items => panic!("unexpected items {:?}", items),
+        }
+    }
+}
@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
 use std::iter::FromIterator;
 
 use super::field_types::{LogicalValue, PhysicalValue, ReportCount, ReportId, ReportSize, Unit, UnitExponent};
+use super::item::Item;
 use super::tag::{Tag, TagType};
-use super::usage::{UsagePage};
+use super::usage::{ExtendedUsage, Usage, UsagePage};
 
 fn replace_option_bool<T: PartialEq>(opt: &mut Option<T>, value: T) -> bool {
     let item_is_new = match opt.as_ref() {
@@ -37,17 +39,17 @@ impl IntoIterator for TagOptimizer {
 
 /// Global state table
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-struct GlobalTable {
-    usage_page: Option<UsagePage>,
-    logical_minimum: Option<LogicalValue>,
-    logical_maximum: Option<LogicalValue>,
-    physical_minimum: Option<PhysicalValue>,
-    physical_maximum: Option<PhysicalValue>,
-    unit_exponent: Option<UnitExponent>,
-    unit: Option<Unit>,
-    report_size: Option<ReportSize>,
-    report_id: Option<ReportId>,
-    report_count: Option<ReportCount>,
+pub(crate) struct GlobalTable {
+    pub(crate) usage_page: Option<UsagePage>,
+    pub(crate) logical_minimum: Option<LogicalValue>,
+    pub(crate) logical_maximum: Option<LogicalValue>,
+    pub(crate) physical_minimum: Option<PhysicalValue>,
+    pub(crate) physical_maximum: Option<PhysicalValue>,
+    pub(crate) unit_exponent: Option<UnitExponent>,
+    pub(crate) unit: Option<Unit>,
+    pub(crate) report_size: Option<ReportSize>,
+    pub(crate) report_id: Option<ReportId>,
+    pub(crate) report_count: Option<ReportCount>,
 }
 
 impl GlobalTable {
@@ -57,7 +59,8 @@ impl GlobalTable {
     }
 
     /// Incorporate a tag into the table. Returns true if the tag was changed.
-    /// Panics if the tag is not recognized.
+    /// Panics if the tag is not recognized. Push and Pop tags are not recognized, as they act on a
+    /// stack of tables.
     pub fn set_tag(&mut self, tag: Tag) -> bool {
         match tag {
             Tag::UsagePage(usage_page) =>
@@ -85,24 +88,59 @@ impl GlobalTable {
     }
 }
 
+/// A GlobalTable saved by a Push tag.
+struct PushedTable {
+    table: GlobalTable,
+    /// Index of the Push tag.
+    index: usize,
+    /// Whether any global item between the Push and its Pop changed the table.
+    is_used: bool,
+}
+
 impl TagOptimizer {
     /// Remove duplicate global attributes.
-    /// Cannot yet handle push and pop tags.
+    /// Push and Pop tags save and restore the state table. A Push and Pop pair is removed when no
+    /// global item between them changes the state.
     pub fn remove_duplicates(mut self) -> Self {
         let mut is_duplicate = Vec::new();
         is_duplicate.resize(self.0.len(), false);
 
         // Maintain a state table of all global items.
         let mut global_table = GlobalTable::new();
-        // Note: we don't have a local table at this time.
-        // Not sure how to handle multiple usages
+        // Tables saved by Push tags.
+        let mut global_stack: Vec<PushedTable> = Vec::new();
+        // Usage pages are chosen by optimize_usages, using a local table.
 
-        for (tag, is_duplicate) in self.0.iter().zip(is_duplicate.iter_mut()) {
-            match tag.tag_type() {
-                TagType::Global => {
-                    *is_duplicate = !global_table.set_tag(*tag);
+        for (index, tag) in self.0.iter().enumerate() {
+            match tag {
+                Tag::Push => {
+                    global_stack.push(PushedTable {
+                        table: global_table,
+                        index,
+                        is_used: false,
+                    });
+                },
+                Tag::Pop => match global_stack.pop() {
+                    Some(pushed) => {
+                        global_table = pushed.table;
+                        // Nothing changed, so nothing needs to be restored.
+                        if !pushed.is_used {
+                            is_duplicate[pushed.index] = true;
+                            is_duplicate[index] = true;
+                        }
+                    },
+                    None => {
+                        // The state after an unmatched Pop is unknown, so forget the state.
+                        global_table = GlobalTable::new();
+                    },
+                },
+                _ => if let TagType::Global = tag.tag_type() {
+                    let is_changed = global_table.set_tag(tag.clone());
+                    is_duplicate[index] = !is_changed;
+                    if let (true, Some(pushed)) = (is_changed, global_stack.last_mut()) {
+                        pushed.is_used = true;
+                    }
                 },
-                _ => (),
             }
         }
 
@@ -117,7 +155,344 @@ impl TagOptimizer {
 }
 
 
+/// Returns the number of bytes needed to encode a tag.
+fn encoded_len(tag: Tag) -> usize {
+    Item::from(tag).encoded_len()
+}
+
+/// Local items which refer to a usage.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum UsageBound {
+    Single,
+    Minimum,
+    Maximum,
+}
+
+/// Ways to encode a usage.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum UsageEncoding {
+    /// A usage ID, relative to the current usage page.
+    PageRelative,
+    /// An extended usage, containing its own usage page.
+    Extended,
+}
+
+impl UsageEncoding {
+    /// Construct the tag encoding a usage.
+    fn tag(self, bound: UsageBound, usage: Usage) -> Tag {
+        let extended_usage = ExtendedUsage::new(usage.as_u32());
+        match (self, bound) {
+            (Self::PageRelative, UsageBound::Single) => Tag::UsageId(usage.id()),
+            (Self::PageRelative, UsageBound::Minimum) => Tag::UsageMinimumId(usage.id()),
+            (Self::PageRelative, UsageBound::Maximum) => Tag::UsageMaximumId(usage.id()),
+            (Self::Extended, UsageBound::Single) => Tag::ExtendedUsage(extended_usage),
+            (Self::Extended, UsageBound::Minimum) => Tag::ExtendedUsageMinimum(extended_usage),
+            (Self::Extended, UsageBound::Maximum) => Tag::ExtendedUsageMaximum(extended_usage),
+        }
+    }
+}
+
+/// One or more usage tags which must be encoded in the same way. Adjacent Usage Minimum and
+/// Usage Maximum tags are grouped, so that both bounds share a usage page.
+#[derive(Clone, Debug)]
+struct UsageUnit {
+    page: UsagePage,
+    usages: Vec<(UsageBound, Usage)>,
+    /// Encoding of a bound which could not be paired. Its encoding is kept so that it still
+    /// matches the other bound.
+    fixed_encoding: Option<UsageEncoding>,
+}
+
+impl UsageUnit {
+    /// Returns the encodings this unit may use, in order of preference.
+    fn encodings(&self) -> &'static [UsageEncoding] {
+        match self.fixed_encoding {
+            Some(UsageEncoding::PageRelative) => &[UsageEncoding::PageRelative],
+            Some(UsageEncoding::Extended) => &[UsageEncoding::Extended],
+            None => &[UsageEncoding::PageRelative, UsageEncoding::Extended],
+        }
+    }
+
+    /// Returns the usage page after this unit, and the number of bytes needed to encode it.
+    fn cost(&self, encoding: UsageEncoding, usage_page: Option<UsagePage>) -> (Option<UsagePage>, usize) {
+        let tags_len: usize = self.usages.iter()
+            .map(|&(bound, usage)| encoded_len(encoding.tag(bound, usage)))
+            .sum();
+        match encoding {
+            UsageEncoding::Extended => (usage_page, tags_len),
+            UsageEncoding::PageRelative if usage_page == Some(self.page) => (usage_page, tags_len),
+            UsageEncoding::PageRelative => {
+                let page_len = encoded_len(Tag::UsagePage(self.page));
+                (Some(self.page), page_len + tags_len)
+            },
+        }
+    }
+}
+
+/// The usage page before a unit, and the encoding of the unit.
+type BackPointer = (Option<UsagePage>, UsageEncoding);
+
+/// An entry in the local table.
+#[derive(Clone, Debug)]
+enum LocalEntry {
+    Tag(Tag),
+    Usages(UsageUnit),
+}
+
+/// Local state table, holding the resolved usages of a run of tags with no Push or Pop. Usage
+/// Page tags are not stored, as they are regenerated when the table is flushed.
+#[derive(Clone, Debug, Default)]
+struct LocalTable(Vec<LocalEntry>);
+
+impl LocalTable {
+    /// Add a tag which does not refer to a usage.
+    fn push_tag(&mut self, tag: Tag) {
+        self.0.push(LocalEntry::Tag(tag));
+    }
+
+    /// Add a usage, given the encoding it was declared with.
+    fn push_usage(&mut self, bound: UsageBound, usage: Usage, declared: UsageEncoding) {
+        // Pair a bound with an adjacent bound on the same page.
+        if let Some(LocalEntry::Usages(unit)) = self.0.last_mut() {
+            let is_pair = matches!(
+                (unit.usages.as_slice(), bound),
+                ([(UsageBound::Minimum, _)], UsageBound::Maximum)
+                    | ([(UsageBound::Maximum, _)], UsageBound::Minimum));
+            if is_pair && unit.page == usage.page() && unit.fixed_encoding == Some(declared) {
+                unit.usages.push((bound, usage));
+                unit.fixed_encoding = None;
+                return;
+            }
+        }
+        let fixed_encoding = match bound {
+            UsageBound::Single => None,
+            UsageBound::Minimum | UsageBound::Maximum => Some(declared),
+        };
+        self.0.push(LocalEntry::Usages(UsageUnit {
+            page: usage.page(),
+            usages: vec![(bound, usage)],
+            fixed_encoding,
+        }));
+    }
+
+    /// Choose the encoding of each unit which minimizes the total size, starting from the given
+    /// usage page.
+    fn choose_encodings(&self, usage_page: Option<UsagePage>) -> Vec<UsageEncoding> {
+        // For each possible usage page, the smallest cost of reaching it.
+        let mut costs = BTreeMap::new();
+        costs.insert(usage_page, 0);
+        // For each unit and each usage page after it, the usage page before it and the encoding
+        // used to reach it at the smallest cost.
+        let mut back_pointers: Vec<BTreeMap<Option<UsagePage>, BackPointer>> = Vec::new();
+
+        let units = self.0.iter().filter_map(|entry| match entry {
+            LocalEntry::Usages(unit) => Some(unit),
+            LocalEntry::Tag(_) => None,
+        });
+        for unit in units {
+            let mut next_costs: BTreeMap<Option<UsagePage>, usize> = BTreeMap::new();
+            let mut steps = BTreeMap::new();
+            for (&page, &cost) in costs.iter() {
+                for &encoding in unit.encodings() {
+                    let (next_page, unit_cost) = unit.cost(encoding, page);
+                    let next_cost = cost + unit_cost;
+                    let is_better = next_costs.get(&next_page)
+                        .is_none_or(|&best_cost| next_cost < best_cost);
+                    if is_better {
+                        next_costs.insert(next_page, next_cost);
+                        steps.insert(next_page, (page, encoding));
+                    }
+                }
+            }
+            costs = next_costs;
+            back_pointers.push(steps);
+        }
+
+        // Follow the back pointers from the cheapest final usage page.
+        let mut page = match costs.into_iter().min_by_key(|&(_, cost)| cost) {
+            Some((page, _)) => page,
+            None => return Vec::new(),
+        };
+        let mut encodings: Vec<UsageEncoding> = back_pointers.iter()
+            .rev()
+            .map(|steps| {
+                let (previous_page, encoding) = steps[&page];
+                page = previous_page;
+                encoding
+            })
+            .collect();
+        encodings.reverse();
+        encodings
+    }
+
+    /// Write out all entries, adding Usage Page tags where needed. Updates the usage page of the
+    /// written tags.
+    fn flush(&mut self, usage_page: &mut Option<UsagePage>, tags: &mut Vec<Tag>) {
+        let mut encodings = self.choose_encodings(*usage_page).into_iter();
+        for entry in self.0.drain(..) {
+            match entry {
+                LocalEntry::Tag(tag) => tags.push(tag),
+                LocalEntry::Usages(unit) => {
+                    let encoding = encodings.next().unwrap();
+                    if encoding == UsageEncoding::PageRelative && *usage_page != Some(unit.page) {
+                        tags.push(Tag::UsagePage(unit.page));
+                        *usage_page = Some(unit.page);
+                    }
+                    for (bound, usage) in unit.usages {
+                        tags.push(encoding.tag(bound, usage));
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl TagOptimizer {
+    /// Choose between a Usage Page and usage ID pair, and an extended usage, for each usage, to
+    /// minimize the total size of the usages and Usage Page tags. Every usage keeps its value.
+    ///
+    /// Usage Page tags are regenerated, so Usage Page tags which do not apply to a usage are
+    /// removed.
+    pub fn optimize_usages(self) -> Self {
+        let mut tags = Vec::new();
+        let mut local_table = LocalTable::default();
+
+        // Usage page of the original tags, used to resolve usage IDs.
+        let mut usage_page: Option<UsagePage> = None;
+        let mut usage_page_stack = Vec::new();
+        // Usage page of the optimized tags.
+        let mut output_page: Option<UsagePage> = None;
+        let mut output_page_stack = Vec::new();
+
+        for tag in self.0 {
+            let page_usage = |id| Usage::new(usage_page.unwrap_or(0), id);
+            let relative = UsageEncoding::PageRelative;
+            let extended = UsageEncoding::Extended;
+            match tag {
+                Tag::UsagePage(page) => usage_page = Some(page),
+                Tag::UsageId(id) =>
+                    local_table.push_usage(UsageBound::Single, page_usage(id), relative),
+                Tag::UsageMinimumId(id) =>
+                    local_table.push_usage(UsageBound::Minimum, page_usage(id), relative),
+                Tag::UsageMaximumId(id) =>
+                    local_table.push_usage(UsageBound::Maximum, page_usage(id), relative),
+                Tag::ExtendedUsage(usage) =>
+                    local_table.push_usage(UsageBound::Single, Usage::from_extended(usage), extended),
+                Tag::ExtendedUsageMinimum(usage) =>
+                    local_table.push_usage(UsageBound::Minimum, Usage::from_extended(usage), extended),
+                Tag::ExtendedUsageMaximum(usage) =>
+                    local_table.push_usage(UsageBound::Maximum, Usage::from_extended(usage), extended),
+                Tag::Push => {
+                    local_table.flush(&mut output_page, &mut tags);
+                    usage_page_stack.push(usage_page);
+                    output_page_stack.push(output_page);
+                    tags.push(tag);
+                },
+                Tag::Pop => {
+                    local_table.flush(&mut output_page, &mut tags);
+                    // The usage page after an unmatched Pop is unknown.
+                    usage_page = usage_page_stack.pop().flatten();
+                    output_page = output_page_stack.pop().flatten();
+                    tags.push(tag);
+                },
+                _ => local_table.push_tag(tag),
+            }
+        }
+        local_table.flush(&mut output_page, &mut tags);
+
+        Self(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::ReportFlags;
+
+    fn optimize_usages(tags: Vec<Tag>) -> Vec<Tag> {
+        TagOptimizer::from_iter(tags).optimize_usages().into_iter().collect()
+    }
+
+    fn remove_duplicates(tags: Vec<Tag>) -> Vec<Tag> {
+        TagOptimizer::from_iter(tags).remove_duplicates().into_iter().collect()
+    }
+
+    #[test]
+    fn uses_extended_usage_for_a_single_usage_on_another_page() {
+        let input = Tag::Input(ReportFlags::from(0x02));
+        let tags = optimize_usages(vec![
+            Tag::UsagePage(0x01),
+            Tag::UsageId(0x30),
+            Tag::UsagePage(0x0C),
+            Tag::UsageId(0xE9),
+            Tag::UsagePage(0x01),
+            Tag::UsageId(0x31),
+            input.clone(),
+        ]);
+        assert_eq!(tags, vec![
+            Tag::UsagePage(0x01),
+            Tag::UsageId(0x30),
+            Tag::ExtendedUsage(ExtendedUsage::new(0x000C_00E9)),
+            Tag::UsageId(0x31),
+            input,
+        ]);
+    }
 
+    #[test]
+    fn switches_pages_for_runs_of_usages() {
+        let mut tags = Vec::new();
+        let mut expected = Vec::new();
+        for page in [0x01, 0x0C, 0x01] {
+            tags.push(Tag::UsagePage(page));
+            expected.push(Tag::UsagePage(page));
+            for id in 1..=3 {
+                tags.push(Tag::ExtendedUsage(ExtendedUsage::new(u32::from(page) << 16 | id)));
+                expected.push(Tag::UsageId(id as u16));
+            }
+        }
+        assert_eq!(optimize_usages(tags), expected);
+    }
 
+    #[test]
+    fn keeps_usage_ranges_on_one_page() {
+        let tags = vec![
+            Tag::UsagePage(0x09),
+            Tag::UsageMinimumId(0x01),
+            Tag::UsageMaximumId(0x08),
+            Tag::Input(ReportFlags::from(0x02)),
+        ];
+        assert_eq!(optimize_usages(tags.clone()), tags);
+    }
 
+    #[test]
+    fn optimizes_long_runs_of_usages() {
+        let tags: Vec<Tag> = (0..2000u32)
+            .map(|index| Tag::ExtendedUsage(ExtendedUsage::new((1 + index % 2) << 16 | index)))
+            .collect();
+        let optimized = optimize_usages(tags.clone());
+        let len = |tags: &[Tag]| tags.iter().cloned().map(encoded_len).sum::<usize>();
+        assert!(len(&optimized) <= len(&tags));
+        assert_eq!(optimized.iter().filter(|tag| matches!(tag.tag_type(), TagType::Local)).count(), 2000);
+    }
 
+    #[test]
+    fn removes_duplicate_globals_and_unused_push_pop_pairs() {
+        let tags = remove_duplicates(vec![
+            Tag::ReportSize(8),
+            Tag::ReportSize(8),
+            Tag::Push,
+            Tag::ReportSize(8),
+            Tag::Pop,
+            Tag::Push,
+            Tag::ReportSize(1),
+            Tag::Pop,
+            Tag::ReportSize(8),
+        ]);
+        assert_eq!(tags, vec![
+            Tag::ReportSize(8),
+            Tag::Push,
+            Tag::ReportSize(1),
+            Tag::Pop,
+        ]);
+    }
+}
@@ -0,0 +1,222 @@
+//! Padding of reports to byte boundaries.
+
+use std::collections::BTreeMap;
+
+use super::collection::{Collection, CollectionItem};
+use super::descriptor::ReportDescriptor;
+use super::field_types::{ReportFlags, ReportId};
+use super::report::{Report, ReportMain, ReportType};
+use super::usage::UsageSet;
+
+/// Reports are laid out separately for each type and ID.
+pub type ReportKey = (ReportType, Option<ReportId>);
+
+/// Where padding is inserted.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PaddingMode {
+    /// Pad each report type and ID once, after its last field group.
+    #[default]
+    EndOfReport,
+    /// Pad after every field group, so that each group starts on a byte boundary.
+    FieldGroup,
+}
+
+/// Construct a constant report which fills the given number of bits after a report.
+///
+/// The padding keeps the global items of the report, so that only the report size and count
+/// need to be written.
+fn padding_after(report: &Report, bit_size: u32) -> Report {
+    Report {
+        main: ReportMain::new(report.report_type(), ReportFlags::new().as_constant().as_variable()),
+        usage_set: UsageSet::empty(),
+        report_size: bit_size,
+        report_count: 1,
+        designator_index: None,
+        designator_minimum: None,
+        designator_maximum: None,
+        string_index: None,
+        string_minimum: None,
+        string_maximum: None,
+        delimiter: None,
+        ..report.clone()
+    }
+}
+
+/// Returns the number of bits needed to bring an offset to a byte boundary.
+fn bits_to_boundary(bit_offset: u64) -> u32 {
+    ((8 - bit_offset % 8) % 8) as u32
+}
+
+/// Returns true if the item is a report with the given type and ID.
+fn continues_group(item: Option<&CollectionItem>, key: ReportKey) -> bool {
+    match item {
+        Some(CollectionItem::Report(report)) => (report.report_type(), report.report_id) == key,
+        _ => false,
+    }
+}
+
+/// Walks the collection tree in descriptor order, tracking the offset of each report type and ID.
+///
+/// A field group is a run of adjacent reports in a collection which share a type and ID. Constant
+/// reports belong to the group they are in.
+#[derive(Default)]
+struct Layout {
+    bit_offsets: BTreeMap<ReportKey, u64>,
+    group_ends: BTreeMap<ReportKey, Vec<u64>>,
+}
+
+impl Layout {
+    /// Add the reports of a collection, recording the end of each field group.
+    fn add_collection(&mut self, collection: &Collection) {
+        let items = collection.items();
+        for (index, item) in items.iter().enumerate() {
+            match item {
+                CollectionItem::Report(report) => {
+                    let key = (report.report_type(), report.report_id);
+                    let bit_offset = self.bit_offsets.entry(key).or_insert(0);
+                    *bit_offset += u64::from(report.report_size) * u64::from(report.report_count);
+                    if !continues_group(items.get(index + 1), key) {
+                        self.group_ends.entry(key).or_default().push(*bit_offset);
+                    }
+                },
+                CollectionItem::Collection(collection) => self.add_collection(collection),
+                CollectionItem::LongItem(_) => (),
+            }
+        }
+    }
+
+    /// Pad the reports of a collection, for field groups which do not end on a byte boundary.
+    ///
+    /// With PaddingMode::EndOfReport, only the last field group of each report type and ID is
+    /// padded. The total size of each report type and ID must already be known.
+    fn pad_collection(&mut self, collection: Collection, mode: PaddingMode, totals: &BTreeMap<ReportKey, u64>) -> Collection {
+        let mut items = collection.items.into_vec().into_iter().peekable();
+        let mut padded_items = Vec::with_capacity(items.len());
+        while let Some(item) = items.next() {
+            match item {
+                CollectionItem::Report(report) => {
+                    let key = (report.report_type(), report.report_id);
+                    let bit_offset = self.bit_offsets.entry(key).or_insert(0);
+                    *bit_offset += u64::from(report.report_size) * u64::from(report.report_count);
+                    let is_padded = match mode {
+                        PaddingMode::FieldGroup => !continues_group(items.peek(), key),
+                        PaddingMode::EndOfReport => totals.get(&key) == Some(bit_offset),
+                    };
+                    let padding_size = bits_to_boundary(*bit_offset);
+                    if is_padded && padding_size > 0 {
+                        *bit_offset += u64::from(padding_size);
+                        let padding = padding_after(&report, padding_size);
+                        padded_items.push(report.into());
+                        padded_items.push(padding.into());
+                    } else {
+                        padded_items.push(report.into());
+                    }
+                },
+                CollectionItem::Collection(subcollection) => {
+                    padded_items.push(self.pad_collection(subcollection, mode, totals).into());
+                },
+                CollectionItem::LongItem(long_item) => padded_items.push(long_item.into()),
+            }
+        }
+        Collection {
+            items: padded_items.into_boxed_slice(),
+            ..collection
+        }
+    }
+}
+
+impl Collection {
+    /// Returns, for each report type and ID, the bit offset at which each field group ends, not
+    /// counting the report ID.
+    ///
+    /// A field group is a run of adjacent reports in a collection which share a type and ID.
+    pub fn field_group_ends(&self) -> BTreeMap<ReportKey, Vec<u64>> {
+        let mut layout = Layout::default();
+        layout.add_collection(self);
+        layout.group_ends
+    }
+
+    /// Insert constant padding reports so that every report type and ID ends on a byte boundary.
+    /// With PaddingMode::FieldGroup, every field group is padded, so that each group starts on a
+    /// byte boundary.
+    ///
+    /// Collections which are already aligned are returned unchanged.
+    pub fn padded(self, mode: PaddingMode) -> Self {
+        let mut totals = Layout::default();
+        totals.add_collection(&self);
+        Layout::default().pad_collection(self, mode, &totals.bit_offsets)
+    }
+}
+
+impl ReportDescriptor {
+    /// Insert constant padding reports so that every report type and ID ends on a byte boundary,
+    /// as by Collection::padded.
+    ///
+    /// Reports with the same type and ID in different collections are laid out as one report, so
+    /// with PaddingMode::EndOfReport, only the last collection using a report type and ID pads it.
+    pub fn padded(self, mode: PaddingMode) -> Self {
+        let mut totals = Layout::default();
+        for collection in self.collections.iter() {
+            totals.add_collection(collection);
+        }
+        let mut layout = Layout::default();
+        self.collections.into_vec()
+            .into_iter()
+            .map(|collection| layout.pad_collection(collection, mode, &totals.bit_offsets))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::CollectionType;
+    use super::super::usage::Usage;
+
+    fn bits(count: u32) -> Report {
+        let usage_set = UsageSet::empty().with_usage(Usage::new(0x09, 0x01));
+        Report::new_input(ReportFlags::new().as_variable(), usage_set, 0, 1, 1, count)
+    }
+
+    fn application(items: Vec<CollectionItem>) -> Collection {
+        Collection::new(CollectionType::Application, Usage::new(0x01, 0x02), items.into_boxed_slice())
+    }
+
+    #[test]
+    fn pads_the_end_of_each_report() {
+        let output = Report { main: ReportMain::new_output(ReportFlags::new().as_variable()), ..bits(2) };
+        let collection = application(vec![bits(3).into(), output.clone().into(), bits(2).into()]);
+        let padded = collection.padded(PaddingMode::EndOfReport);
+        assert_eq!(padded.items(), &[
+            bits(3).into(),
+            output.clone().into(),
+            padding_after(&output, 6).into(),
+            bits(2).into(),
+            padding_after(&bits(2), 3).into(),
+        ]);
+    }
+
+    #[test]
+    fn pads_each_field_group() {
+        let nested = Collection::new(CollectionType::Physical, Usage::new(0x01, 0x01), (bits(3),));
+        let collection = application(vec![bits(4).into(), nested.into(), bits(8).into()]);
+        assert_eq!(collection.field_group_ends().get(&(ReportType::Input, None)), Some(&vec![4, 7, 15]));
+
+        let padded = collection.padded(PaddingMode::FieldGroup);
+        assert_eq!(padded.field_group_ends().get(&(ReportType::Input, None)), Some(&vec![8, 16, 24]));
+    }
+
+    #[test]
+    fn keeps_aligned_collections() {
+        let collection = application(vec![bits(5).into(), bits(3).into()]);
+        assert_eq!(collection.clone().padded(PaddingMode::FieldGroup), collection);
+    }
+
+    #[test]
+    fn pads_reports_shared_by_collections_once() {
+        let descriptor = ReportDescriptor::new([application(vec![bits(3).into()]), application(vec![bits(2).into()])]);
+        let padded = descriptor.padded(PaddingMode::EndOfReport);
+        assert_eq!(padded.collections()[0].items(), &[bits(3).into()]);
+        assert_eq!(padded.collections()[1].items(), &[bits(2).into(), padding_after(&bits(2), 3).into()]);
+    }
+}
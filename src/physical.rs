@@ -0,0 +1,266 @@
+//! Conversion between logical values and physical quantities, as described in 6.2.2.7 of the USB
+//! HID specification.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use super::format::{FieldAttributes, ReportVariable};
+use super::unit::Unit;
+
+/// A physical value with its unit. The value includes the unit exponent, so a value of 2.5 with a
+/// unit of cm is 2.5 cm.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhysicalQuantity {
+    pub value: f64,
+    pub unit: Unit,
+}
+
+impl PhysicalQuantity {
+    pub const fn new(value: f64, unit: Unit) -> Self {
+        Self { value, unit }
+    }
+}
+
+/// How a physical value between two logical values is rounded when encoding.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Rounding {
+    /// Round to the nearest logical value, with halves rounded away from zero.
+    #[default]
+    Nearest,
+    /// Round towards negative infinity.
+    Down,
+    /// Round towards positive infinity.
+    Up,
+    /// Round towards zero.
+    TowardZero,
+}
+
+impl Rounding {
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            Self::Nearest => value.round(),
+            Self::Down => value.floor(),
+            Self::Up => value.ceil(),
+            Self::TowardZero => value.trunc(),
+        }
+    }
+}
+
+/// How a physical value outside of the physical range is handled when encoding.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Clamping {
+    /// Fail with ConversionError::OutOfRange.
+    #[default]
+    Reject,
+    /// Use the nearest bound of the logical range.
+    Clamp,
+}
+
+/// Error type when a physical quantity cannot be encoded as a logical value.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConversionError {
+    /// The quantity has a different unit than the field.
+    UnitMismatch {
+        expected: Unit,
+        found: Unit,
+    },
+    /// The value is infinite or NaN.
+    NotFinite,
+    /// The value is outside of the physical range, and was not clamped.
+    OutOfRange,
+    /// The physical range is a single value while the logical range is not, so a physical value
+    /// does not select a single logical value.
+    DegenerateRange,
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnitMismatch { expected, found } =>
+                write!(fmt, "expected a quantity in {}, found {}", expected, found),
+            Self::NotFinite => "physical value is not finite".fmt(fmt),
+            Self::OutOfRange => "physical value out of range".fmt(fmt),
+            Self::DegenerateRange => "physical range holds a single value".fmt(fmt),
+        }
+    }
+}
+impl Error for ConversionError {}
+
+
+impl FieldAttributes {
+    /// Returns the unit of values of this field. A missing unit is no unit.
+    pub fn unit_or_default(&self) -> Unit {
+        self.unit.unwrap_or(Unit::NONE)
+    }
+
+    /// Apply the unit exponent to a value, or remove it when inverse is true. A missing exponent
+    /// is 0.
+    fn scale(&self, value: f64, inverse: bool) -> f64 {
+        let exponent = self.unit_exponent.map_or(0, |exponent| exponent.as_i8());
+        // Divide by positive powers of 10, rather than multiplying by negative powers, so that
+        // values such as 2540 at an exponent of -2 are exact.
+        let factor = 10f64.powi(exponent.unsigned_abs().into());
+        if (exponent < 0) != inverse {
+            value / factor
+        } else {
+            value * factor
+        }
+    }
+
+    /// Returns the physical minimum and maximum, before the unit exponent is applied.
+    ///
+    /// Missing bounds are 0. If both bounds are 0, the logical bounds are used instead.
+    pub fn physical_bounds(&self) -> (i64, i64) {
+        let minimum = self.physical_minimum.unwrap_or(0);
+        let maximum = self.physical_maximum.unwrap_or(0);
+        if minimum == 0 && maximum == 0 {
+            (self.logical_minimum.into(), self.logical_maximum.into())
+        } else {
+            (minimum.into(), maximum.into())
+        }
+    }
+
+    /// Convert a logical value into a physical quantity.
+    pub fn to_physical(&self, logical: i64) -> PhysicalQuantity {
+        let (physical_minimum, physical_maximum) = self.physical_bounds();
+        let logical_minimum = i64::from(self.logical_minimum);
+        let logical_range = i64::from(self.logical_maximum) - logical_minimum;
+        let value = if logical_range == 0 {
+            physical_minimum as f64
+        } else {
+            let resolution = (physical_maximum - physical_minimum) as f64 / logical_range as f64;
+            physical_minimum as f64 + (logical - logical_minimum) as f64 * resolution
+        };
+        PhysicalQuantity::new(self.scale(value, false), self.unit_or_default())
+    }
+
+    /// Convert a physical quantity into a logical value, rounding it to the nearest logical value
+    /// in the direction given, and either clamping or rejecting values outside of the range.
+    pub fn to_logical(
+        &self,
+        quantity: PhysicalQuantity,
+        rounding: Rounding,
+        clamping: Clamping,
+    ) -> Result<i64, ConversionError> {
+        if quantity.unit != self.unit_or_default() {
+            return Err(ConversionError::UnitMismatch {
+                expected: self.unit_or_default(),
+                found: quantity.unit,
+            });
+        }
+        if !quantity.value.is_finite() {
+            return Err(ConversionError::NotFinite);
+        }
+
+        let (physical_minimum, physical_maximum) = self.physical_bounds();
+        let logical_minimum = i64::from(self.logical_minimum);
+        let logical_maximum = i64::from(self.logical_maximum);
+        let physical_range = physical_maximum - physical_minimum;
+        let logical_range = logical_maximum - logical_minimum;
+        let value = self.scale(quantity.value, true);
+
+        let logical = if logical_range == 0 {
+            logical_minimum as f64
+        } else if physical_range == 0 {
+            return Err(ConversionError::DegenerateRange);
+        } else {
+            let resolution = logical_range as f64 / physical_range as f64;
+            rounding.apply(logical_minimum as f64 + (value - physical_minimum as f64) * resolution)
+        };
+
+        let lower = logical_minimum.min(logical_maximum);
+        let upper = logical_minimum.max(logical_maximum);
+        if (lower as f64..=upper as f64).contains(&logical) {
+            Ok(logical as i64)
+        } else {
+            match clamping {
+                Clamping::Clamp => Ok((logical as i64).clamp(lower, upper)),
+                Clamping::Reject => Err(ConversionError::OutOfRange),
+            }
+        }
+    }
+}
+
+impl ReportVariable {
+    /// Returns the value of this variable as a physical quantity.
+    pub fn get_physical(&self) -> PhysicalQuantity {
+        self.attributes().to_physical(self.get_value())
+    }
+
+    /// Set this variable from a physical quantity, scaling it into the logical range.
+    pub fn set_physical(
+        &mut self,
+        quantity: PhysicalQuantity,
+        rounding: Rounding,
+        clamping: Clamping,
+    ) -> Result<(), ConversionError> {
+        let logical = self.attributes().to_logical(quantity, rounding, clamping)?;
+        self.set_value(logical).map_err(|_| ConversionError::OutOfRange)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::UnitExponent;
+
+    /// A field of 0 to 255 covering 0 to 25.40 inches.
+    fn inches() -> FieldAttributes {
+        FieldAttributes {
+            logical_minimum: 0,
+            logical_maximum: 255,
+            physical_minimum: Some(0),
+            physical_maximum: Some(2540),
+            unit: Some(Unit::INCH),
+            unit_exponent: Some(UnitExponent::try_from(-2i8).unwrap()),
+            ..FieldAttributes::default()
+        }
+    }
+
+    #[test]
+    fn converts_logical_values_to_physical_quantities() {
+        assert_eq!(inches().to_physical(0), PhysicalQuantity::new(0.0, Unit::INCH));
+        assert_eq!(inches().to_physical(255), PhysicalQuantity::new(25.4, Unit::INCH));
+
+        let no_physical_range = FieldAttributes { physical_maximum: Some(0), ..inches() };
+        assert_eq!(no_physical_range.physical_bounds(), (0, 255));
+        assert_eq!(no_physical_range.to_physical(100), PhysicalQuantity::new(1.0, Unit::INCH));
+    }
+
+    #[test]
+    fn rounds_physical_quantities_to_logical_values() {
+        let half_inch = PhysicalQuantity::new(12.7, Unit::INCH);
+        let to_logical = |rounding| inches().to_logical(half_inch, rounding, Clamping::Reject);
+        assert_eq!(to_logical(Rounding::Nearest), Ok(128));
+        assert_eq!(to_logical(Rounding::Up), Ok(128));
+        assert_eq!(to_logical(Rounding::Down), Ok(127));
+        assert_eq!(to_logical(Rounding::TowardZero), Ok(127));
+    }
+
+    #[test]
+    fn rejects_or_clamps_invalid_quantities() {
+        let to_logical = |value, clamping| {
+            inches().to_logical(PhysicalQuantity::new(value, Unit::INCH), Rounding::Nearest, clamping)
+        };
+        assert_eq!(to_logical(30.0, Clamping::Reject), Err(ConversionError::OutOfRange));
+        assert_eq!(to_logical(30.0, Clamping::Clamp), Ok(255));
+        assert_eq!(to_logical(-1.0, Clamping::Clamp), Ok(0));
+        assert_eq!(to_logical(f64::NAN, Clamping::Clamp), Err(ConversionError::NotFinite));
+
+        let centimeters = PhysicalQuantity::new(1.0, Unit::CENTIMETER);
+        assert_eq!(inches().to_logical(centimeters, Rounding::Nearest, Clamping::Clamp),
+            Err(ConversionError::UnitMismatch { expected: Unit::INCH, found: Unit::CENTIMETER }));
+
+        let degenerate = FieldAttributes { physical_minimum: Some(5), physical_maximum: Some(5), ..inches() };
+        assert_eq!(degenerate.to_logical(PhysicalQuantity::new(0.05, Unit::INCH), Rounding::Nearest, Clamping::Clamp),
+            Err(ConversionError::DegenerateRange));
+    }
+
+    #[test]
+    fn sets_variables_from_physical_quantities() {
+        let mut variable = ReportVariable::new(0, 8).unwrap().with_attributes(inches());
+        variable.set_physical(PhysicalQuantity::new(25.4, Unit::INCH), Rounding::Nearest, Clamping::Reject).unwrap();
+        assert_eq!(variable.get_unsigned(), 255);
+        assert_eq!(variable.get_physical(), PhysicalQuantity::new(25.4, Unit::INCH));
+    }
+}
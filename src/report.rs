@@ -1,7 +1,7 @@
 use super::field_types::{Delimiter, DesignatorIndex, LogicalValue, PhysicalValue, ReportCount, ReportFlags, ReportId, ReportSize, StringIndex, Unit, UnitExponent};
 use super::usage::UsageSet;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum ReportType {
     Input,
     Output,
@@ -11,27 +11,52 @@ pub enum ReportType {
 impl ReportType {
     /// Returns true if this ReportType is an Input.
     pub const fn is_input(self) -> bool {
-        match self {
-            ReportType::Input => true,
-            _ => false,
-        }
+        matches!(self, ReportType::Input)
     }
 
     /// Returns true if this ReportType is an Output.
     pub const fn is_output(self) -> bool {
-        match self {
-            ReportType::Output => true,
-            _ => false,
-        }
+        matches!(self, ReportType::Output)
     }
 
     /// Returns true if this ReportType is a Feature.
     pub const fn is_feature(self) -> bool {
-        match self {
-            ReportType::Feature => true,
-            _ => false,
+        matches!(self, ReportType::Feature)
+    }
+}
+
+/// A set of report types.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReportTypes {
+    pub input: bool,
+    pub output: bool,
+    pub feature: bool,
+}
+
+impl ReportTypes {
+    /// Returns true if the set contains the given type.
+    pub const fn contains(self, report_type: ReportType) -> bool {
+        match report_type {
+            ReportType::Input => self.input,
+            ReportType::Output => self.output,
+            ReportType::Feature => self.feature,
         }
     }
+
+    /// Add a type to the set.
+    pub fn insert(&mut self, report_type: ReportType) {
+        match report_type {
+            ReportType::Input => self.input = true,
+            ReportType::Output => self.output = true,
+            ReportType::Feature => self.feature = true,
+        }
+    }
+
+    /// Return the set with the given type added.
+    pub fn with(mut self, report_type: ReportType) -> Self {
+        self.insert(report_type);
+        self
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -114,7 +139,7 @@ impl Report {
     ) -> Self {
         Self {
             main,
-            usage_set: usage_set,
+            usage_set,
             logical_minimum,
             logical_maximum,
             report_size,
@@ -26,7 +26,7 @@ impl <'a> TagGroupIterator<'a> {
 }
 
 impl TagGroup {
-    pub fn tags(&self) -> TagGroupIterator {
+    pub fn tags(&self) -> TagGroupIterator<'_> {
         match self {
             TagGroup::Tag(tag) => TagGroupIterator::new_over_tag(tag),
             TagGroup::Group(items) => TagGroupIterator::new_over_group(items.into_iter()),
@@ -1,4 +1,5 @@
 mod iter;
+#[allow(clippy::module_inception)]
 mod tag;
 
 pub use tag::{Tag, TagGroup, TagType};
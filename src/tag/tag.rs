@@ -1,5 +1,6 @@
 use super::super::collection::{Collection, CollectionItem};
 use super::super::field_types::{ReportFlags, CollectionType, Delimiter, DesignatorIndex, LogicalValue, PhysicalValue, ReportCount, ReportId, ReportSize, StringIndex, Unit, UnitExponent};
+use super::super::item::{Item, LongItem};
 use super::super::report::{Report, ReportMain, ReportType};
 use super::super::usage::{ExtendedUsage, Usage, UsageId, UsagePage, UsageRange, UsageSet};
 
@@ -7,10 +8,11 @@ pub enum TagType {
     Main,
     Global,
     Local,
+    Long,
 }
 
 /// A tag defines a single statement in a HID report.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Tag {
     // Main items
     Input(ReportFlags),
@@ -48,6 +50,9 @@ pub enum Tag {
     StringMinimum(StringIndex),
     StringMaximum(StringIndex),
     Delimiter(Delimiter),
+
+    // Long items
+    LongItem(LongItem),
 }
 
 impl Tag {
@@ -85,10 +90,21 @@ impl Tag {
             Self::StringMinimum(..) => TagType::Local,
             Self::StringMaximum(..) => TagType::Local,
             Self::Delimiter(..) => TagType::Local,
+
+            Self::LongItem(..) => TagType::Long,
         }
     }
 }
 
+/// Returns the number of bytes needed to encode the local tags of a TagGroup, not counting any
+/// Usage Page tags.
+fn tag_group_len(tag_group: &TagGroup) -> usize {
+    tag_group.tags()
+        .filter(|tag| !matches!(tag, Tag::UsagePage(..)))
+        .map(|tag| Item::from(tag.clone()).encoded_len())
+        .sum()
+}
+
 /// A TagGroup allows tags to be arranged in a hierarchical structure. However, they are still
 /// ordered and can be flattened afterwards into a linear tag document.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -197,6 +213,12 @@ impl TagGroup {
     pub const fn delimiter(v: Delimiter) -> Self {
         Self::Tag(Tag::Delimiter(v))
     }
+
+    // Long items
+
+    pub const fn long_item(v: LongItem) -> Self {
+        Self::Tag(Tag::LongItem(v))
+    }
 }
 
 
@@ -256,10 +278,33 @@ impl TagGroup {
         Self::group(tag_group)
     }
 
+    /// Construct a tag group to represent a range of usages in its shortest form: either as a
+    /// Usage Minimum and Usage Maximum, or as a list of usages.
+    pub fn shortest_usage_range(usage_range: UsageRange) -> Self {
+        let bounds_len = tag_group_len(&Self::usage_range(usage_range));
+        // Stop counting once the list is known to be longer.
+        let mut list_len = 0;
+        let is_list_shorter = usage_range.usages()
+            .all(|usage| {
+                list_len += tag_group_len(&Self::usage(usage));
+                list_len <= bounds_len
+            });
+        if is_list_shorter {
+            Self::group(usage_range.usages().map(Self::usage))
+        } else {
+            Self::usage_range(usage_range)
+        }
+    }
+
     /// Construct a tag group to represent a UsageSet.
+    /// Unless the UsageSet is preserved, consecutive usages are merged, and each range is written
+    /// in its shortest form.
     pub fn usage_set(usage_set: UsageSet) -> Self {
-        let tag_group = usage_set.into_iter().map(Self::usage_range);
-        Self::group(tag_group)
+        if usage_set.is_preserved() {
+            Self::group(usage_set.into_iter().map(Self::usage_range))
+        } else {
+            Self::group(usage_set.compacted().into_iter().map(Self::shortest_usage_range))
+        }
     }
 
     /// Construct a tag group to represent a collection.
@@ -310,8 +355,6 @@ impl TagGroup {
             Self::report_count(report.report_count),
         ]);
 
-        // TODO: Optionals
-
         // Optional global fields
 
         // Physical Minimum
@@ -339,6 +382,27 @@ impl TagGroup {
             tag_groups.push(Self::report_id(report_id))
         }
 
+        // Optional local fields
+
+        if let Some(designator_index) = report.designator_index {
+            tag_groups.push(Self::designator_index(designator_index))
+        }
+        if let Some(designator_minimum) = report.designator_minimum {
+            tag_groups.push(Self::designator_minimum(designator_minimum))
+        }
+        if let Some(designator_maximum) = report.designator_maximum {
+            tag_groups.push(Self::designator_maximum(designator_maximum))
+        }
+        if let Some(string_index) = report.string_index {
+            tag_groups.push(Self::string_index(string_index))
+        }
+        if let Some(string_minimum) = report.string_minimum {
+            tag_groups.push(Self::string_minimum(string_minimum))
+        }
+        if let Some(string_maximum) = report.string_maximum {
+            tag_groups.push(Self::string_maximum(string_maximum))
+        }
+
         // Add the report main
         tag_groups.push(Self::report_main(report.main));
 
@@ -349,6 +413,7 @@ impl TagGroup {
         match item {
             CollectionItem::Report(report) => Self::report(report),
             CollectionItem::Collection(collection) => Self::collection(collection),
+            CollectionItem::LongItem(long_item) => Self::long_item(long_item),
         }
     }
 
@@ -357,3 +422,34 @@ impl TagGroup {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_tags(tag_group: TagGroup) -> Vec<Tag> {
+        tag_group.tags().filter(|tag| !matches!(tag, Tag::UsagePage(..))).cloned().collect()
+    }
+
+    #[test]
+    fn writes_usage_ranges_in_their_shortest_form() {
+        // Two usages take as many bytes as their bounds, and are listed.
+        let pair = UsageRange::new(Usage::new(0x09, 1), Usage::new(0x09, 2));
+        assert_eq!(local_tags(TagGroup::shortest_usage_range(pair)), [Tag::UsageId(1), Tag::UsageId(2)]);
+
+        let triple = UsageRange::new(Usage::new(0x09, 1), Usage::new(0x09, 3));
+        assert_eq!(local_tags(TagGroup::shortest_usage_range(triple)), [Tag::UsageMinimumId(1), Tag::UsageMaximumId(3)]);
+    }
+
+    #[test]
+    fn merges_usage_sets_unless_preserved() {
+        let usage_set = UsageSet::empty()
+            .with_usage(Usage::new(0x09, 1))
+            .with_usage(Usage::new(0x09, 2))
+            .with_usage(Usage::new(0x09, 3));
+        assert_eq!(local_tags(TagGroup::usage_set(usage_set.clone())),
+                   [Tag::UsageMinimumId(1), Tag::UsageMaximumId(3)]);
+        assert_eq!(local_tags(TagGroup::usage_set(usage_set.preserved())),
+                   [Tag::UsageId(1), Tag::UsageId(2), Tag::UsageId(3)]);
+    }
+}
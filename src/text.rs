@@ -0,0 +1,422 @@
+//! Textual report descriptors, with one tag on each line as written by Listing, such as
+//! "Usage Page (Generic Desktop)" or "Input (Data,Var,Abs)".
+//!
+//! Indentation is ignored when parsing, and comments start with "//". Usages, pages and numbers
+//! without a name may be written in hexadecimal. A usage with more than four hexadecimal digits is
+//! an extended usage, which includes its page.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use super::field_types::{CollectionType, Delimiter, ReportFlags, Unit, UnitExponent, UnitExponentEncoding};
+use super::item::LongItem;
+use super::listing::Listing;
+use super::tag::Tag;
+use super::usage::{ExtendedUsage, UsageId, UsagePage};
+use super::usage_tables::{page_from_name, usage_from_name};
+
+/// Reason a line of text could not be parsed into a tag.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextErrorKind {
+    /// The name of the item is not known.
+    UnknownItem,
+    /// The item needs an argument in parentheses.
+    MissingArgument,
+    /// The item does not take an argument.
+    UnexpectedArgument,
+    /// The argument is not valid for the item.
+    InvalidArgument,
+    /// An opening parenthesis is not closed.
+    UnclosedParenthesis,
+    /// Characters follow the closing parenthesis.
+    TrailingCharacters,
+}
+
+impl Display for TextErrorKind {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownItem => "unknown item".fmt(fmt),
+            Self::MissingArgument => "missing argument".fmt(fmt),
+            Self::UnexpectedArgument => "unexpected argument".fmt(fmt),
+            Self::InvalidArgument => "invalid argument".fmt(fmt),
+            Self::UnclosedParenthesis => "unclosed parenthesis".fmt(fmt),
+            Self::TrailingCharacters => "trailing characters".fmt(fmt),
+        }
+    }
+}
+
+/// Error type when a textual descriptor cannot be parsed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TextError {
+    /// Line at which parsing failed, starting at 1.
+    pub line: usize,
+    /// Column at which parsing failed, in characters, starting at 1.
+    pub column: usize,
+    /// Reason parsing failed.
+    pub kind: TextErrorKind,
+}
+
+impl TextError {
+    /// Construct a TextError at the given line and column.
+    pub const fn new(line: usize, column: usize, kind: TextErrorKind) -> Self {
+        Self { line, column, kind }
+    }
+}
+
+impl Display for TextError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} at line {}, column {}", self.kind, self.line, self.column)
+    }
+}
+impl Error for TextError {}
+
+
+/// Parse an integer, written in decimal or with a "0x" prefix in hexadecimal, with an optional
+/// sign.
+fn parse_integer(text: &str) -> Option<i64> {
+    let (is_negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let magnitude = match unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X")) {
+        Some(digits) => i64::from_str_radix(digits, 16).ok()?,
+        None => unsigned.parse().ok()?,
+    };
+    Some(if is_negative { -magnitude } else { magnitude })
+}
+
+/// Parse an integer into a given type, failing if it is out of range.
+fn parse_number<T: TryFrom<i64>>(text: &str) -> Option<T> {
+    parse_integer(text).and_then(|value| T::try_from(value).ok())
+}
+
+/// Returns true if the text is a hexadecimal number with more than four digits, which can only be
+/// an extended usage.
+fn is_extended_hex(text: &str) -> bool {
+    text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))
+        .is_some_and(|digits| digits.len() > 4)
+}
+
+fn parse_usage_page(text: &str) -> Option<UsagePage> {
+    page_from_name(text).or_else(|| parse_number(text))
+}
+
+fn parse_usage_id(usage_page: Option<UsagePage>, text: &str) -> Option<UsageId> {
+    usage_page.and_then(|usage_page| usage_from_name(usage_page, text))
+        .map(|usage| usage.id())
+        .or_else(|| parse_number(text))
+}
+
+/// Returns true if the text starts with the name of a usage page followed by ':', as an extended
+/// usage is written. Usage names may also hold ':', such as "Motion: Accelerometer 3D".
+fn has_page_prefix(text: &str) -> bool {
+    text.split_once(':').is_some_and(|(page, _)| page_from_name(page.trim()).is_some())
+}
+
+/// Parse an extended usage, written as "Page: Usage" or as a hexadecimal number.
+fn parse_extended_usage(text: &str) -> Option<ExtendedUsage> {
+    match text.split_once(':') {
+        Some((page, name)) => {
+            let usage = usage_from_name(page_from_name(page.trim())?, name.trim())?;
+            Some(ExtendedUsage::new(usage.as_u32()))
+        },
+        None => parse_number(text).map(ExtendedUsage::new),
+    }
+}
+
+/// Parse report flags written as by Display, such as "Data,Var,Abs". Flags which are not given
+/// keep their default value, and the default value of a flag may also be written. Other bits,
+/// such as reserved bits, may be given as a number.
+fn parse_report_flags(text: &str) -> Option<ReportFlags> {
+    text.split(',').try_fold(ReportFlags::new(), |flags, flag| {
+        let flag = flag.trim();
+        let flags = match flag.to_ascii_lowercase().as_str() {
+            "data" => flags.as_data(),
+            "const" | "cnst" | "constant" => flags.as_constant(),
+            "array" | "ary" => flags.as_array(),
+            "var" | "variable" => flags.as_variable(),
+            "abs" | "absolute" => flags.as_absolute(),
+            "rel" | "relative" => flags.as_relative(),
+            "no wrap" => flags.without_wrap(),
+            "wrap" => flags.with_wrap(),
+            "linear" | "lin" => flags.as_linear(),
+            "nonlinear" | "non linear" | "nlin" => flags.as_nonlinear(),
+            "preferred state" | "pref" => flags.with_preferred_state(),
+            "no preferred state" | "no preferred" | "npref" => flags.without_preferred_state(),
+            "no null position" | "no null" => flags.without_null_position(),
+            "null state" | "null" => flags.with_null_state(),
+            "non volatile" | "nonvolatile" => flags.as_nonvolatile(),
+            "volatile" | "vol" => flags.as_volatile(),
+            "bit field" | "bitfield" => flags.as_bit_field(),
+            "buffered bytes" | "buf" => flags.as_buffered_bytes(),
+            _ => ReportFlags::from(u32::from(flags) | parse_number::<u32>(flag)?),
+        };
+        Some(flags)
+    })
+}
+
+/// Parse a unit exponent, written as its value followed by its encoding, "nibble" or "byte", which
+/// defaults to nibble.
+fn parse_unit_exponent(text: &str) -> Option<UnitExponent> {
+    let (value, encoding) = match text.split_once(',') {
+        Some((value, encoding)) => {
+            let encoding = match encoding.trim().to_ascii_lowercase().as_str() {
+                "nibble" => UnitExponentEncoding::Nibble,
+                "byte" => UnitExponentEncoding::SignedByte,
+                _ => return None,
+            };
+            (value.trim(), encoding)
+        },
+        None => (text, UnitExponentEncoding::Nibble),
+    };
+    let unit_exponent = UnitExponent::try_from(parse_number::<i8>(value)?).ok()?;
+    Some(unit_exponent.with_encoding(encoding))
+}
+
+fn parse_collection_type(text: &str) -> Option<CollectionType> {
+    let collection_type = match text.to_ascii_lowercase().as_str() {
+        "physical" => CollectionType::Physical,
+        "application" => CollectionType::Application,
+        "logical" => CollectionType::Logical,
+        "report" => CollectionType::Report,
+        "named array" => CollectionType::NamedArray,
+        "usage switch" => CollectionType::UsageSwitch,
+        "usage modifier" => CollectionType::UsageModifier,
+        _ => CollectionType::try_from(parse_number::<u8>(text)?).ok()?,
+    };
+    Some(collection_type)
+}
+
+fn parse_delimiter(text: &str) -> Option<Delimiter> {
+    match text.to_ascii_lowercase().as_str() {
+        "open" | "1" => Some(Delimiter::open()),
+        "close" | "0" => Some(Delimiter::close()),
+        _ => None,
+    }
+}
+
+/// Parse a long item, written as its tag followed by its data bytes, separated by commas.
+fn parse_long_item(text: &str) -> Option<LongItem> {
+    let mut values = text.split(',').map(|value| parse_number::<u8>(value.trim()));
+    let tag = values.next()??;
+    let data = values.collect::<Option<Vec<u8>>>()?;
+    LongItem::new(tag, &data).ok()
+}
+
+/// Parses lines into tags, tracking the usage page so that usages can be named.
+#[derive(Default)]
+struct TextParser {
+    usage_page: Option<UsagePage>,
+    saved_usage_pages: Vec<Option<UsagePage>>,
+}
+
+impl TextParser {
+    /// Parse the tag named by an item, given its argument if any.
+    fn parse_tag(&mut self, name: &str, argument: Option<&str>) -> Result<Tag, TextErrorKind> {
+        use TextErrorKind::{InvalidArgument, MissingArgument, UnexpectedArgument, UnknownItem};
+
+        let name = name.to_ascii_lowercase();
+        // Items without an argument.
+        let tag = match name.as_str() {
+            "end collection" => Some(Tag::EndCollection),
+            "push" => Some(Tag::Push),
+            "pop" => Some(Tag::Pop),
+            _ => None,
+        };
+        if let Some(tag) = tag {
+            if argument.is_some() {
+                return Err(UnexpectedArgument);
+            }
+            match tag {
+                Tag::Push => self.saved_usage_pages.push(self.usage_page),
+                Tag::Pop => if let Some(usage_page) = self.saved_usage_pages.pop() {
+                    self.usage_page = usage_page;
+                },
+                _ => (),
+            }
+            return Ok(tag);
+        }
+
+        let is_known = matches!(name.as_str(),
+            "input" | "output" | "feature" | "collection" | "usage page" | "logical minimum"
+            | "logical maximum" | "physical minimum" | "physical maximum" | "unit exponent"
+            | "unit" | "report size" | "report id" | "report count" | "usage" | "usage minimum"
+            | "usage maximum" | "designator index" | "designator minimum" | "designator maximum"
+            | "string index" | "string minimum" | "string maximum" | "delimiter" | "long item");
+        if !is_known {
+            return Err(UnknownItem);
+        }
+        let argument = argument.ok_or(MissingArgument)?;
+
+        // Items with an argument.
+        // A name on the current usage page is read first, as it may hold ':' itself.
+        let usage = |tag: fn(UsageId) -> Tag, extended_tag: fn(ExtendedUsage) -> Tag| {
+            let named = self.usage_page.and_then(|usage_page| usage_from_name(usage_page, argument));
+            if let Some(usage) = named {
+                Some(tag(usage.id()))
+            } else if has_page_prefix(argument) || is_extended_hex(argument) {
+                parse_extended_usage(argument).map(extended_tag)
+            } else {
+                parse_usage_id(self.usage_page, argument).map(tag)
+            }
+        };
+        let tag = match name.as_str() {
+            "input" => parse_report_flags(argument).map(Tag::Input),
+            "output" => parse_report_flags(argument).map(Tag::Output),
+            "feature" => parse_report_flags(argument).map(Tag::Feature),
+            "collection" => parse_collection_type(argument).map(Tag::Collection),
+
+            "usage page" => parse_usage_page(argument).map(Tag::UsagePage),
+            "logical minimum" => parse_number(argument).map(Tag::LogicalMinimum),
+            "logical maximum" => parse_number(argument).map(Tag::LogicalMaximum),
+            "physical minimum" => parse_number(argument).map(Tag::PhysicalMinimum),
+            "physical maximum" => parse_number(argument).map(Tag::PhysicalMaximum),
+            "unit exponent" => parse_unit_exponent(argument).map(Tag::UnitExponent),
+            "unit" => Unit::from_str(argument).ok().map(Tag::Unit),
+            "report size" => parse_number(argument).map(Tag::ReportSize),
+            "report id" => parse_number(argument).map(Tag::ReportId),
+            "report count" => parse_number(argument).map(Tag::ReportCount),
+
+            "usage" => usage(Tag::UsageId, Tag::ExtendedUsage),
+            "usage minimum" => usage(Tag::UsageMinimumId, Tag::ExtendedUsageMinimum),
+            "usage maximum" => usage(Tag::UsageMaximumId, Tag::ExtendedUsageMaximum),
+            "designator index" => parse_number::<u32>(argument).map(|value| Tag::DesignatorIndex(value.into())),
+            "designator minimum" => parse_number::<u32>(argument).map(|value| Tag::DesignatorMinimum(value.into())),
+            "designator maximum" => parse_number::<u32>(argument).map(|value| Tag::DesignatorMaximum(value.into())),
+            "string index" => parse_number::<u32>(argument).map(|value| Tag::StringIndex(value.into())),
+            "string minimum" => parse_number::<u32>(argument).map(|value| Tag::StringMinimum(value.into())),
+            "string maximum" => parse_number::<u32>(argument).map(|value| Tag::StringMaximum(value.into())),
+            "delimiter" => parse_delimiter(argument).map(Tag::Delimiter),
+
+            "long item" => parse_long_item(argument).map(Tag::LongItem),
+            _ => unreachable!(),
+        }.ok_or(InvalidArgument)?;
+
+        if let Tag::UsagePage(usage_page) = tag {
+            self.usage_page = Some(usage_page);
+        }
+        Ok(tag)
+    }
+
+    /// Parse a line of text, which is either blank or holds a single tag.
+    /// Errors are reported with the column of the offending text, starting at 1.
+    fn parse_line(&mut self, line: &str) -> Result<Option<Tag>, (usize, TextErrorKind)> {
+        let line = line.split("//").next().unwrap_or_default();
+        // Every part is a slice of the line, so its column is found from its position.
+        let column_of = |part: &str| {
+            let offset = part.as_ptr() as usize - line.as_ptr() as usize;
+            line[..offset].chars().count() + 1
+        };
+
+        let text = line.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let name_column = column_of(line.trim_start());
+
+        let (name, argument) = match text.split_once('(') {
+            Some((name, rest)) => {
+                let (argument, trailing) = rest.rsplit_once(')')
+                    .ok_or((column_of(rest) - 1, TextErrorKind::UnclosedParenthesis))?;
+                if !trailing.trim().is_empty() {
+                    return Err((column_of(trailing.trim_start()), TextErrorKind::TrailingCharacters));
+                }
+                (name.trim_end(), Some(argument))
+            },
+            None => (text, None),
+        };
+
+        self.parse_tag(name, argument.map(str::trim))
+            .map(Some)
+            .map_err(|kind| {
+                let column = match (kind, argument) {
+                    (TextErrorKind::InvalidArgument | TextErrorKind::UnexpectedArgument, Some(argument)) =>
+                        column_of(argument.trim_start()),
+                    _ => name_column,
+                };
+                (column, kind)
+            })
+    }
+}
+
+impl FromStr for Listing {
+    type Err = TextError;
+
+    /// Parse a listing written as by Display, with one tag on each line.
+    fn from_str(text: &str) -> Result<Self, TextError> {
+        let mut parser = TextParser::default();
+        let mut tags = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            match parser.parse_line(line) {
+                Ok(Some(tag)) => tags.push(tag),
+                Ok(None) => (),
+                Err((column, kind)) => return Err(TextError::new(index + 1, column, kind)),
+            }
+        }
+        Ok(tags.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::usage::Usage;
+    use super::super::usage_tables::PAGE_TABLES;
+
+    /// Write the listing of the bytes as text, and parse it back into bytes.
+    fn round_trip(bytes: &[u8]) -> (String, Box<[u8]>) {
+        let text = Listing::from_bytes(bytes).unwrap().to_string();
+        let parsed = Listing::from_str(&text).unwrap().into_bytes();
+        (text, parsed)
+    }
+
+    #[test]
+    fn round_trips_unit_exponent_encodings() {
+        // Unit Exponent (-1) as a signed byte, then as a nibble.
+        let bytes = [0x55, 0xFF, 0x55, 0x0F];
+        let (text, parsed) = round_trip(&bytes);
+        assert_eq!(text, "Unit Exponent (-1, byte)\nUnit Exponent (-1)\n");
+        assert_eq!(&*parsed, &bytes);
+    }
+
+    #[test]
+    fn round_trips_reserved_report_flags() {
+        // Input (Data,Var,Abs) with reserved bit 9 set.
+        let bytes = [0x82, 0x02, 0x02];
+        let (text, parsed) = round_trip(&bytes);
+        assert_eq!(text, "Input (Data,Var,Abs,0x200)\n");
+        assert_eq!(&*parsed, &bytes);
+    }
+
+    #[test]
+    fn parses_names_and_numbers() {
+        let listing = Listing::from_str("Usage Page (Generic Desktop)\nUsage (Mouse) // comment\nUsage (0x31)").unwrap();
+        assert_eq!(&*listing.into_bytes(), &[0x05, 0x01, 0x09, 0x02, 0x09, 0x31]);
+    }
+
+    #[test]
+    fn round_trips_every_named_usage() {
+        for table in PAGE_TABLES {
+            for &(usage_id, _) in table.usages {
+                let usage = ExtendedUsage::new(Usage::new(table.id, usage_id).as_u32());
+                let tags = [
+                    Tag::UsagePage(table.id),
+                    Tag::UsageId(usage_id),
+                    Tag::UsageMinimumId(usage_id),
+                    Tag::UsageMaximumId(usage_id),
+                    Tag::ExtendedUsage(usage),
+                ];
+                let text = tags.iter().cloned().collect::<Listing>().to_string();
+                let parsed = Listing::from_str(&text).unwrap_or_else(|error| panic!("{}: {}", text, error));
+                assert_eq!(parsed.tags(), &tags, "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_the_column_of_invalid_arguments() {
+        let error = Listing::from_str("Report Size (8)\n  Unit Exponent (-1, word)").unwrap_err();
+        assert_eq!(error, TextError::new(2, 18, TextErrorKind::InvalidArgument));
+    }
+}
@@ -0,0 +1,470 @@
+//! Units of physical values, as described in 6.2.2.7 of the USB HID specification.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// The system of measurement of a Unit, held in its lowest nibble.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum UnitSystem {
+    None,
+    SiLinear,
+    SiRotation,
+    EnglishLinear,
+    EnglishRotation,
+    Vendor,
+    /// A reserved system, holding its nibble.
+    Reserved(u8),
+}
+
+impl UnitSystem {
+    /// Return the nibble of this UnitSystem.
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::None => 0x0,
+            Self::SiLinear => 0x1,
+            Self::SiRotation => 0x2,
+            Self::EnglishLinear => 0x3,
+            Self::EnglishRotation => 0x4,
+            Self::Vendor => 0xF,
+            Self::Reserved(code) => code & 0xF,
+        }
+    }
+
+    /// Construct a UnitSystem from the lower 4 bits of a u8.
+    pub const fn from_nibble(nibble: u8) -> Self {
+        match nibble & 0xF {
+            0x0 => Self::None,
+            0x1 => Self::SiLinear,
+            0x2 => Self::SiRotation,
+            0x3 => Self::EnglishLinear,
+            0x4 => Self::EnglishRotation,
+            0xF => Self::Vendor,
+            code => Self::Reserved(code),
+        }
+    }
+
+    /// Returns true if units in this system have symbols, which is the case for the SI and English
+    /// systems.
+    pub const fn is_standard(self) -> bool {
+        matches!(self, Self::SiLinear | Self::SiRotation | Self::EnglishLinear | Self::EnglishRotation)
+    }
+}
+
+
+/// A unit is divided into segments (nibbles) of 4 bits. Each segment corresponds to a different
+/// type of unit: system, length, mass, time, temperature, current, and luminosity intensity. The
+/// last segment is reserved.
+/// The value in each segment is interpreted in the table on page 37. Apart from the system, each
+/// segment holds a signed 4-bit exponent, from -8 to 7.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Unit(u32);
+
+/// Sign-extend the nibble at the given position.
+const fn exponent_at(code: u32, position: u32) -> i8 {
+    ((((code >> (position * 4)) & 0xF) as u8) << 4) as i8 >> 4
+}
+
+/// Replace the nibble at the given position with an exponent.
+const fn with_exponent_at(code: u32, position: u32, exponent: i8) -> u32 {
+    assert!(-8 <= exponent && exponent <= 7, "unit exponents range from -8 to 7");
+    let shift = position * 4;
+    (code & !(0xF << shift)) | ((exponent as u8 as u32 & 0xF) << shift)
+}
+
+macro_rules! impl_unit_exponents(
+    ($(($position:expr, $get:ident, $set:ident)),* $(,)?) => {
+        impl Unit {
+            $(
+            /// Return the exponent of this dimension, from -8 to 7.
+            pub const fn $get(self) -> i8 {
+                exponent_at(self.0, $position)
+            }
+            /// Return this unit with the exponent of this dimension replaced.
+            ///
+            /// Panics if the exponent is not in the range -8 to 7.
+            pub const fn $set(self, exponent: i8) -> Self {
+                Self(with_exponent_at(self.0, $position, exponent))
+            }
+            )*
+        }
+    }
+);
+
+impl_unit_exponents!(
+    (1, length, with_length),
+    (2, mass, with_mass),
+    (3, time, with_time),
+    (4, temperature, with_temperature),
+    (5, current, with_current),
+    (6, luminous_intensity, with_luminous_intensity),
+);
+
+impl Unit {
+    /// No unit.
+    pub const NONE: Self = Self(0);
+
+    pub const CENTIMETER: Self = Self::new(UnitSystem::SiLinear).with_length(1);
+    pub const RADIAN: Self = Self::new(UnitSystem::SiRotation).with_length(1);
+    pub const INCH: Self = Self::new(UnitSystem::EnglishLinear).with_length(1);
+    pub const DEGREE: Self = Self::new(UnitSystem::EnglishRotation).with_length(1);
+    pub const GRAM: Self = Self::new(UnitSystem::SiLinear).with_mass(1);
+    pub const SLUG: Self = Self::new(UnitSystem::EnglishLinear).with_mass(1);
+    pub const SECOND: Self = Self::new(UnitSystem::SiLinear).with_time(1);
+    pub const KELVIN: Self = Self::new(UnitSystem::SiLinear).with_temperature(1);
+    pub const FAHRENHEIT: Self = Self::new(UnitSystem::EnglishLinear).with_temperature(1);
+    pub const AMPERE: Self = Self::new(UnitSystem::SiLinear).with_current(1);
+    pub const CANDELA: Self = Self::new(UnitSystem::SiLinear).with_luminous_intensity(1);
+
+    /// s⁻¹
+    pub const HERTZ: Self = Self::new(UnitSystem::SiLinear).with_time(-1);
+    /// cm·s⁻¹
+    pub const CENTIMETER_PER_SECOND: Self = Self::CENTIMETER.with_time(-1);
+    /// cm·s⁻²
+    pub const CENTIMETER_PER_SECOND_SQUARED: Self = Self::CENTIMETER.with_time(-2);
+    /// rad·s⁻¹
+    pub const RADIAN_PER_SECOND: Self = Self::RADIAN.with_time(-1);
+    /// rad·s⁻²
+    pub const RADIAN_PER_SECOND_SQUARED: Self = Self::RADIAN.with_time(-2);
+    /// in·s⁻¹
+    pub const INCH_PER_SECOND: Self = Self::INCH.with_time(-1);
+    /// deg·s⁻¹
+    pub const DEGREE_PER_SECOND: Self = Self::DEGREE.with_time(-1);
+    /// Force, in cm·g·s⁻².
+    pub const DYNE: Self = Self::CENTIMETER.with_mass(1).with_time(-2);
+    /// Energy, in cm²·g·s⁻².
+    pub const ERG: Self = Self::CENTIMETER.with_length(2).with_mass(1).with_time(-2);
+    /// Voltage, in cm²·g·s⁻³·A⁻¹.
+    pub const VOLT: Self = Self::ERG.with_time(-3).with_current(-1);
+
+    /// Construct a Unit in the given system, with all exponents 0.
+    pub const fn new(system: UnitSystem) -> Self {
+        Self(system.code() as u32)
+    }
+
+    pub const fn code(self) -> u32 {
+        self.0
+    }
+
+    /// Return the system of this Unit.
+    pub const fn system(self) -> UnitSystem {
+        UnitSystem::from_nibble((self.0 & 0xF) as u8)
+    }
+
+    /// Return this unit with the system replaced.
+    pub const fn with_system(self, system: UnitSystem) -> Self {
+        Self((self.0 & !0xF) | system.code() as u32)
+    }
+
+    /// Return the exponents of length, mass, time, temperature, current and luminous intensity,
+    /// in that order.
+    pub const fn exponents(self) -> [i8; 6] {
+        [
+            self.length(),
+            self.mass(),
+            self.time(),
+            self.temperature(),
+            self.current(),
+            self.luminous_intensity(),
+        ]
+    }
+
+    /// Returns true if the reserved nibble of this Unit is set.
+    pub const fn has_reserved_bits(self) -> bool {
+        self.0 >> 28 != 0
+    }
+}
+
+impl From<u32> for Unit {
+    fn from(code: u32) -> Self {
+        Self(code)
+    }
+}
+
+impl From<Unit> for u32 {
+    fn from(unit: Unit) -> Self {
+        unit.code()
+    }
+}
+
+
+// Symbols of each dimension, in the order of their segments, for each standard system.
+const SI_LINEAR_SYMBOLS: [&str; 6] = ["cm", "g", "s", "K", "A", "cd"];
+const SI_ROTATION_SYMBOLS: [&str; 6] = ["rad", "g", "s", "K", "A", "cd"];
+const ENGLISH_LINEAR_SYMBOLS: [&str; 6] = ["in", "slug", "s", "°F", "A", "cd"];
+const ENGLISH_ROTATION_SYMBOLS: [&str; 6] = ["deg", "slug", "s", "°F", "A", "cd"];
+
+/// Standard systems with their names, in the order preferred when parsing symbols shared by
+/// several systems.
+const STANDARD_SYSTEMS: [(UnitSystem, &str, [&str; 6]); 4] = [
+    (UnitSystem::SiLinear, "SI linear", SI_LINEAR_SYMBOLS),
+    (UnitSystem::SiRotation, "SI rotation", SI_ROTATION_SYMBOLS),
+    (UnitSystem::EnglishLinear, "English linear", ENGLISH_LINEAR_SYMBOLS),
+    (UnitSystem::EnglishRotation, "English rotation", ENGLISH_ROTATION_SYMBOLS),
+];
+
+/// Returns the system in which symbols of the given dimensions are read when no system is named:
+/// the first standard system which has the symbol of each dimension.
+fn implied_system(exponents: [i8; 6], symbols: &[&str; 6]) -> Option<UnitSystem> {
+    STANDARD_SYSTEMS.iter()
+        .find(|(_, _, system_symbols)| {
+            exponents.iter()
+                .zip(symbols.iter().zip(system_symbols))
+                .all(|(&exponent, (symbol, system_symbol))| exponent == 0 || symbol == system_symbol)
+        })
+        .map(|(system, _, _)| *system)
+}
+
+const SUPERSCRIPT_DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+const SUPERSCRIPT_MINUS: char = '⁻';
+
+impl Display for Unit {
+    /// Write this Unit as symbols with superscript exponents, such as "cm·s⁻²".
+    ///
+    /// A Unit of 0 is written as "none". Units outside of the SI and English systems, or with
+    /// reserved bits, are written as their code in hexadecimal.
+    ///
+    /// When the symbols are shared with a system which is preferred when parsing, the system is
+    /// written first, as in "SI rotation: s⁻¹", so that the Unit is parsed back unchanged.
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return "none".fmt(fmt);
+        }
+        let system = STANDARD_SYSTEMS.iter()
+            .find(|(system, _, _)| *system == self.system());
+        let (name, symbols) = match system {
+            Some((_, name, symbols)) if !self.has_reserved_bits() => (name, symbols),
+            _ => return write!(fmt, "{:#010x}", self.0),
+        };
+        if self.exponents() == [0; 6] {
+            // A standard system without any dimensions.
+            return write!(fmt, "{:#010x}", self.0);
+        }
+        if implied_system(self.exponents(), symbols) != Some(self.system()) {
+            write!(fmt, "{}: ", name)?;
+        }
+
+        let mut is_first = true;
+        for (symbol, exponent) in symbols.iter().zip(self.exponents()) {
+            if exponent == 0 {
+                continue;
+            }
+            if !is_first {
+                '·'.fmt(fmt)?;
+            }
+            is_first = false;
+            symbol.fmt(fmt)?;
+            if exponent != 1 {
+                if exponent < 0 {
+                    SUPERSCRIPT_MINUS.fmt(fmt)?;
+                }
+                SUPERSCRIPT_DIGITS[exponent.unsigned_abs() as usize].fmt(fmt)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Error type when text cannot be parsed as a Unit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseUnitError {
+    /// The text is empty.
+    Empty,
+    /// The symbol is not a unit of any system.
+    UnknownSymbol(String),
+    /// The named system is not a standard system.
+    UnknownSystem(String),
+    /// The exponent of the given symbol is not a number from -8 to 7.
+    InvalidExponent(String),
+    /// The given symbol appears more than once.
+    RepeatedSymbol(String),
+    /// The symbols do not all belong to a single system.
+    MixedSystems,
+}
+
+impl Display for ParseUnitError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => "empty unit".fmt(fmt),
+            Self::UnknownSymbol(symbol) => write!(fmt, "unknown unit symbol \"{}\"", symbol),
+            Self::UnknownSystem(name) => write!(fmt, "unknown unit system \"{}\"", name),
+            Self::InvalidExponent(symbol) => write!(fmt, "invalid exponent on \"{}\"", symbol),
+            Self::RepeatedSymbol(symbol) => write!(fmt, "unit symbol \"{}\" repeated", symbol),
+            Self::MixedSystems => "unit symbols from different systems".fmt(fmt),
+        }
+    }
+}
+impl Error for ParseUnitError {}
+
+/// Parse an exponent, written either in superscript or after a '^'. An empty exponent is 1.
+fn parse_exponent(text: &str) -> Option<i8> {
+    if text.is_empty() {
+        return Some(1);
+    }
+    if let Some(ascii) = text.strip_prefix('^') {
+        return ascii.parse().ok();
+    }
+    let (is_negative, digits) = match text.strip_prefix(SUPERSCRIPT_MINUS) {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let mut value: i8 = 0;
+    for c in digits.chars() {
+        let digit = SUPERSCRIPT_DIGITS.iter().position(|&d| d == c)?;
+        value = value.checked_mul(10)?.checked_add(digit as i8)?;
+    }
+    match (digits.is_empty(), is_negative) {
+        (true, _) => None,
+        (false, true) => Some(-value),
+        (false, false) => Some(value),
+    }
+}
+
+impl FromStr for Unit {
+    type Err = ParseUnitError;
+
+    /// Parse a Unit written as by Display, such as "cm·s⁻²" or "SI rotation: s⁻¹".
+    ///
+    /// Terms may also be separated by '*', and exponents may be written as "^-2". Symbols shared
+    /// by several systems, such as "s", are read in the SI linear system unless the system is
+    /// named, or another symbol selects a different system.
+    fn from_str(text: &str) -> Result<Self, ParseUnitError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(ParseUnitError::Empty);
+        }
+        if text == "none" {
+            return Ok(Self::NONE);
+        }
+        if let Some(hex) = text.strip_prefix("0x") {
+            return u32::from_str_radix(hex, 16)
+                .map(Self)
+                .map_err(|_| ParseUnitError::UnknownSymbol(text.into()));
+        }
+
+        // Systems which contain every symbol seen so far.
+        let mut systems = [true; 4];
+        let text = match text.split_once(':') {
+            Some((name, symbols)) => {
+                let name = name.trim();
+                let named = STANDARD_SYSTEMS.iter()
+                    .position(|(_, system_name, _)| system_name.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| ParseUnitError::UnknownSystem(name.into()))?;
+                systems = [false; 4];
+                systems[named] = true;
+                symbols.trim()
+            },
+            None => text,
+        };
+
+        let mut exponents: [Option<i8>; 6] = [None; 6];
+        for term in text.split(['·', '*']).map(str::trim) {
+            let symbol_end = term.find(|c: char| c == '^' || c == SUPERSCRIPT_MINUS
+                                       || SUPERSCRIPT_DIGITS.contains(&c))
+                .unwrap_or(term.len());
+            let (symbol, exponent) = term.split_at(symbol_end);
+            let exponent = parse_exponent(exponent)
+                .filter(|exponent| (-8..=7).contains(exponent))
+                .ok_or_else(|| ParseUnitError::InvalidExponent(symbol.into()))?;
+
+            let mut dimension = None;
+            for (is_possible, (_, _, symbols)) in systems.iter_mut().zip(STANDARD_SYSTEMS.iter()) {
+                match symbols.iter().position(|&s| s == symbol) {
+                    Some(position) => dimension = Some(position),
+                    None => *is_possible = false,
+                }
+            }
+            let dimension = dimension.ok_or_else(|| ParseUnitError::UnknownSymbol(symbol.into()))?;
+            // A symbol of another system is reported before a repeated dimension, as "cm·deg"
+            // repeats the length dimension only because deg is not an SI linear unit.
+            if !systems.contains(&true) {
+                return Err(ParseUnitError::MixedSystems);
+            }
+            if exponents[dimension].replace(exponent).is_some() {
+                return Err(ParseUnitError::RepeatedSymbol(symbol.into()));
+            }
+        }
+
+        let system = systems.iter()
+            .zip(STANDARD_SYSTEMS.iter())
+            .find(|(is_possible, _)| **is_possible)
+            .map(|(_, (system, _, _))| *system)
+            .ok_or(ParseUnitError::MixedSystems)?;
+        let exponents = exponents.map(|exponent| exponent.unwrap_or(0));
+        Ok(Self::new(system)
+            .with_length(exponents[0])
+            .with_mass(exponents[1])
+            .with_time(exponents[2])
+            .with_temperature(exponents[3])
+            .with_current(exponents[4])
+            .with_luminous_intensity(exponents[5]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_symbols_with_exponents() {
+        assert_eq!(Unit::NONE.to_string(), "none");
+        assert_eq!(Unit::CENTIMETER_PER_SECOND_SQUARED.to_string(), "cm·s⁻²");
+        assert_eq!(Unit::VOLT.to_string(), "cm²·g·s⁻³·A⁻¹");
+        assert_eq!(Unit::DEGREE_PER_SECOND.to_string(), "deg·s⁻¹");
+        assert_eq!(Unit::HERTZ.to_string(), "s⁻¹");
+        assert_eq!(Unit::from(0xF000_0011).to_string(), "0xf0000011");
+        assert_eq!(Unit::new(UnitSystem::Vendor).with_length(1).to_string(), "0x0000001f");
+        assert_eq!(Unit::new(UnitSystem::SiRotation).to_string(), "0x00000002");
+    }
+
+    #[test]
+    fn names_the_system_when_symbols_are_shared() {
+        let per_second = Unit::new(UnitSystem::SiRotation).with_time(-1);
+        assert_eq!(per_second.to_string(), "SI rotation: s⁻¹");
+        assert_eq!("SI rotation: s⁻¹".parse(), Ok(per_second));
+        assert_eq!(Unit::new(UnitSystem::EnglishLinear).with_current(1).to_string(), "English linear: A");
+        assert_eq!(Unit::new(UnitSystem::EnglishRotation).with_mass(1).to_string(), "English rotation: slug");
+        assert_eq!(Unit::SLUG.to_string(), "slug");
+        assert_eq!("english rotation: deg".parse(), Ok(Unit::DEGREE));
+    }
+
+    #[test]
+    fn parses_every_written_unit() {
+        let systems = [UnitSystem::SiLinear, UnitSystem::SiRotation, UnitSystem::EnglishLinear, UnitSystem::EnglishRotation];
+        for system in systems {
+            for first in 1..=6 {
+                for second in first..=6 {
+                    for exponent in -8..=7 {
+                        let code = Unit::new(system).code()
+                            | (exponent as u8 as u32 & 0xF) << (first * 4)
+                            | 1 << (second * 4);
+                        let unit = Unit::from(code);
+                        assert_eq!(unit.to_string().parse(), Ok(unit), "{}", unit);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_alternative_forms() {
+        assert_eq!("cm*s^-2".parse(), Ok(Unit::CENTIMETER_PER_SECOND_SQUARED));
+        assert_eq!(" rad · s⁻¹ ".parse(), Ok(Unit::RADIAN_PER_SECOND));
+        assert_eq!("0x00F0D121".parse(), Ok(Unit::from(0x00F0_D121)));
+    }
+
+    #[test]
+    fn rejects_invalid_units() {
+        assert_eq!("".parse::<Unit>(), Err(ParseUnitError::Empty));
+        assert_eq!("m".parse::<Unit>(), Err(ParseUnitError::UnknownSymbol("m".into())));
+        assert_eq!("cm⁹".parse::<Unit>(), Err(ParseUnitError::InvalidExponent("cm".into())));
+        assert_eq!("s·s".parse::<Unit>(), Err(ParseUnitError::RepeatedSymbol("s".into())));
+        assert_eq!("cm·deg".parse::<Unit>(), Err(ParseUnitError::MixedSystems));
+        assert_eq!("deg·cm".parse::<Unit>(), Err(ParseUnitError::MixedSystems));
+        assert_eq!("deg·deg".parse::<Unit>(), Err(ParseUnitError::RepeatedSymbol("deg".into())));
+        assert_eq!("cm·slug".parse::<Unit>(), Err(ParseUnitError::MixedSystems));
+        assert_eq!("SI rotation: cm".parse::<Unit>(), Err(ParseUnitError::MixedSystems));
+        assert_eq!("metric: cm".parse::<Unit>(), Err(ParseUnitError::UnknownSystem("metric".into())));
+    }
+}
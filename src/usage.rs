@@ -94,7 +94,7 @@ impl Eq for Usage {}
 
 impl PartialOrd for Usage {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.as_u32().partial_cmp(&other.as_u32())
+        Some(self.cmp(other))
     }
 }
 
@@ -141,6 +141,21 @@ impl UsageRange {
     pub const fn len(&self) -> u32 {
         self.max.as_u32() + 1 - self.min.as_u32()
     }
+
+    /// Returns true if this range contains no usages. A UsageRange always holds at least one
+    /// usage.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over every usage in this range, in order.
+    pub fn usages(self) -> impl Iterator<Item = Usage> {
+        let min = self.min;
+        (self.min.as_u32()..=self.max.as_u32()).map(move |value| match min {
+            Usage::Standard(page, _) => Usage::new(page, (value & 0xFFFF) as UsageId),
+            Usage::Extended(_) => Usage::extended(value),
+        })
+    }
 }
 
 impl From<Usage> for UsageRange {
@@ -151,21 +166,109 @@ impl From<Usage> for UsageRange {
 }
 
 /// A UsageSet is an ordered collection of Usages in a report.
+///
+/// When compiled, consecutive usages are merged into ranges and each range is written in its
+/// shortest form. A preserved UsageSet is instead written exactly as declared.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
-pub struct UsageSet(Vec<UsageRange>);
+pub struct UsageSet {
+    ranges: Vec<UsageRange>,
+    is_preserved: bool,
+}
 
 impl UsageSet {
     pub fn empty() -> Self {
-        Self(Vec::new())
+        Self::default()
     }
 
     pub fn into_boxed_slice(self) -> Box<[UsageRange]> {
-        self.0.into_boxed_slice()
+        self.ranges.into_boxed_slice()
+    }
+
+    /// Returns the number of usages in this UsageSet, counting every usage in each range.
+    pub fn len(&self) -> u32 {
+        self.ranges.iter().map(UsageRange::len).sum()
+    }
+
+    /// Returns true if this UsageSet contains no usages.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns an iterator over every usage in this UsageSet, in order.
+    pub fn usages(&self) -> impl Iterator<Item = Usage> + '_ {
+        self.ranges.iter().flat_map(|range| range.usages())
+    }
+
+    /// Returns the usage at the given position in this UsageSet, counting every usage in each
+    /// range.
+    pub fn get(&self, index: u32) -> Option<Usage> {
+        let mut remaining = index;
+        for range in self.ranges.iter() {
+            if remaining < range.len() {
+                return range.usages().nth(remaining as usize);
+            }
+            remaining -= range.len();
+        }
+        None
+    }
+
+    /// Returns the position of the first occurrence of a usage in this UsageSet, counting every
+    /// usage in each range.
+    pub fn position(&self, usage: Usage) -> Option<u32> {
+        let mut index = 0;
+        for range in self.ranges.iter() {
+            if range.min <= usage && usage <= range.max {
+                return Some(index + usage.as_u32() - range.min.as_u32());
+            }
+            index += range.len();
+        }
+        None
+    }
+
+    /// Move all usages of another UsageSet to the end of this UsageSet.
+    /// The result is preserved if either UsageSet was preserved.
+    pub fn append(&mut self, other: &mut UsageSet) {
+        self.ranges.append(&mut other.ranges);
+        self.is_preserved |= other.is_preserved;
+    }
+
+    /// Returns true if this UsageSet should be written exactly as declared.
+    pub const fn is_preserved(&self) -> bool {
+        self.is_preserved
+    }
+
+    /// Mark this UsageSet to be written exactly as declared, without merging or splitting any
+    /// ranges. Useful when a host depends on the declared form of the usages.
+    pub fn preserved(mut self) -> Self {
+        self.is_preserved = true;
+        self
+    }
+
+    /// Merge each range with the following range when the following range continues it on the
+    /// same page. The order of usages is unchanged. A preserved UsageSet is returned unchanged.
+    pub fn compacted(self) -> Self {
+        if self.is_preserved {
+            return self;
+        }
+        let mut ranges: Vec<UsageRange> = Vec::with_capacity(self.ranges.len());
+        for range in self.ranges {
+            if let Some(last) = ranges.last_mut() {
+                let is_continued = last.max.as_u32().checked_add(1) == Some(range.min.as_u32())
+                    && discriminant(&last.max) == discriminant(&range.min)
+                    && last.max.page() == range.min.page();
+                if is_continued {
+                    last.max = range.max;
+                    continue;
+                }
+            }
+            ranges.push(range);
+        }
+        Self { ranges, ..self }
     }
 
     /// Add a Usage to this UsageSet.
     pub fn push_usage(&mut self, usage: Usage) {
-        self.0.push(UsageRange::single(usage))
+        self.ranges.push(UsageRange::single(usage))
     }
 
     /// Add a Usage to this UsageSet, returning this UsageSet.
@@ -176,7 +279,7 @@ impl UsageSet {
 
     /// Add a Usage range to this UsageSet.
     pub fn push_usage_range(&mut self, usage_range: UsageRange) {
-        self.0.push(usage_range)
+        self.ranges.push(usage_range)
     }
 
     /// Add a Usage range to this UsageSet, returning this UsageSet.
@@ -187,7 +290,7 @@ impl UsageSet {
 
     /// Add a Usage range, specified by a minimum and maximum Usage, to this UsageSet.
     pub fn push_usage_bounds(&mut self, min: Usage, max: Usage) {
-        self.0.push(UsageRange::new(min, max))
+        self.ranges.push(UsageRange::new(min, max))
     }
 
     /// Add a Usage range, specified by a minimum and maximum Usage, to this UsageSet, and return
@@ -204,7 +307,46 @@ impl IntoIterator for UsageSet {
 
     /// Consume this UsageSet and produce an interator over its UsageRanges.
     fn into_iter(self) -> IntoIter<UsageRange> {
-        self.0.into_iter()
+        self.ranges.into_iter()
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(usage_set: UsageSet) -> Vec<(u32, u32)> {
+        usage_set.into_iter().map(|range| (range.min.as_u32(), range.max.as_u32())).collect()
+    }
+
+    #[test]
+    fn compacts_consecutive_usages_on_a_page() {
+        let usage_set = UsageSet::empty()
+            .with_usage(Usage::new(0x09, 1))
+            .with_usage(Usage::new(0x09, 2))
+            .with_usage_bounds(Usage::new(0x09, 3), Usage::new(0x09, 5))
+            .with_usage(Usage::new(0x09, 7))
+            .with_usage(Usage::new(0x0A, 1));
+        assert_eq!(ranges(usage_set.clone().compacted()), [
+            (0x0009_0001, 0x0009_0005),
+            (0x0009_0007, 0x0009_0007),
+            (0x000A_0001, 0x000A_0001),
+        ]);
+        assert_eq!(usage_set.len(), 7);
+        assert_eq!(usage_set.usages().nth(4), Some(Usage::new(0x09, 5)));
+    }
+
+    #[test]
+    fn keeps_preserved_usage_sets() {
+        let usage_set = UsageSet::empty()
+            .with_usage(Usage::new(0x09, 1))
+            .with_usage(Usage::new(0x09, 2))
+            .preserved();
+        assert_eq!(usage_set.clone().compacted(), usage_set);
+
+        let mut appended = UsageSet::empty().with_usage(Usage::new(0x09, 3));
+        appended.append(&mut usage_set.clone());
+        assert!(appended.is_preserved());
+    }
+}
@@ -1,4 +1,6 @@
-use super::usage::{Usage, UsagePage};
+use std::borrow::Cow;
+
+use super::usage::{Usage, UsageId, UsagePage};
 
 // Usage IDs.
 // From the HID Usages and Descriptions document.
@@ -21,6 +23,7 @@ pub mod page {
     pub const DIGITIZERS: UsagePage = 0x000D;
     pub const HAPTICS: UsagePage = 0x000E;
     pub const PHYSICAL_INPUT_DEVICE: UsagePage = 0x000F;
+    /// Withdrawn from the HID Usage Tables, so it has no name in PAGE_TABLES.
     pub const UNICODE: UsagePage = 0x0010;
     pub const SOC: UsagePage = 0x0011;
     pub const EYE_AND_HEAD_TRACKERS: UsagePage = 0x0012;
@@ -47,6 +50,7 @@ pub mod page {
 
     pub const CAMERA_CONTROL: UsagePage = 0x0090;
     pub const ARCADE: UsagePage = 0x0091;
+    /// Not in the HID Usage Tables, so it has no name in PAGE_TABLES.
     pub const GAMING_DEVICE: UsagePage = 0x0092;
 
     pub const FIDO_ALLIANCE: UsagePage = 0xF1D0;
@@ -204,4 +208,132 @@ pub mod button {
     pub fn new(button: u16) -> Usage {
         Usage::new(page::BUTTON, button)
     }
-}
\ No newline at end of file
+}
+
+// Names of every usage, generated by build.rs from the machine-readable HID Usage Tables in
+// data/HidUsageTables.json. To update the tables, replace that file with a newer one from usb.org.
+
+/// A usage page of the HID Usage Tables, with the names of its usages.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PageTable {
+    pub id: UsagePage,
+    pub name: &'static str,
+    /// Named usages, sorted by ID.
+    pub usages: &'static [(UsageId, &'static str)],
+    /// Usages named by their number, such as "Button 1".
+    pub generator: Option<UsageGenerator>,
+}
+
+/// Names a range of usages by their number, after a prefix.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UsageGenerator {
+    pub name_prefix: &'static str,
+    pub start: UsageId,
+    pub end: UsageId,
+}
+
+impl UsageGenerator {
+    /// Returns true if the generator names the given usage ID.
+    pub const fn contains(&self, usage_id: UsageId) -> bool {
+        self.start <= usage_id && usage_id <= self.end
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/usage_tables.rs"));
+
+impl PageTable {
+    /// Returns the name of a usage on this page.
+    pub fn usage_name(&self, usage_id: UsageId) -> Option<Cow<'static, str>> {
+        if let Ok(index) = self.usages.binary_search_by_key(&usage_id, |(id, _)| *id) {
+            return Some(Cow::Borrowed(self.usages[index].1));
+        }
+        self.generator
+            .filter(|generator| generator.contains(usage_id))
+            .map(|generator| Cow::Owned(format!("{} {}", generator.name_prefix, usage_id)))
+    }
+
+    /// Returns the ID of the usage with the given name on this page. Case is ignored.
+    pub fn usage_id(&self, name: &str) -> Option<UsageId> {
+        let named = self.usages.iter()
+            .find(|(_, usage_name)| usage_name.eq_ignore_ascii_case(name))
+            .map(|(id, _)| *id);
+        named.or_else(|| {
+            let generator = self.generator?;
+            let (prefix, number) = name.split_once(' ')?;
+            let usage_id = number.parse().ok()?;
+            let is_generated = prefix.eq_ignore_ascii_case(generator.name_prefix) && generator.contains(usage_id);
+            is_generated.then_some(usage_id)
+        })
+    }
+}
+
+/// Returns the table of a usage page.
+pub fn page_table(usage_page: UsagePage) -> Option<&'static PageTable> {
+    PAGE_TABLES.binary_search_by_key(&usage_page, |table| table.id)
+        .ok()
+        .map(|index| &PAGE_TABLES[index])
+}
+
+/// Returns the name of a usage page, such as "Generic Desktop".
+pub fn page_name(usage_page: UsagePage) -> Option<&'static str> {
+    page_table(usage_page).map(|table| table.name)
+}
+
+/// Returns the name of a usage within its page, such as "Mouse" or "Button 1".
+pub fn usage_name(usage: Usage) -> Option<Cow<'static, str>> {
+    page_table(usage.page())?.usage_name(usage.id())
+}
+
+/// Returns the usage page with the given name, as by page_name. Case is ignored.
+pub fn page_from_name(name: &str) -> Option<UsagePage> {
+    PAGE_TABLES.iter()
+        .find(|table| table.name.eq_ignore_ascii_case(name))
+        .map(|table| table.id)
+}
+
+/// Returns the usage with the given name within a usage page, as by usage_name. Case is ignored.
+pub fn usage_from_name(usage_page: UsagePage, name: &str) -> Option<Usage> {
+    let usage_id = page_table(usage_page)?.usage_id(name)?;
+    Some(Usage::new(usage_page, usage_id))
+}
+
+/// Returns the usage with the given name within the usage page with the given name, such as
+/// ("Generic Desktop", "Mouse"). Case is ignored.
+pub fn usage_from_names(page_name: &str, usage_name: &str) -> Option<Usage> {
+    usage_from_name(page_from_name(page_name)?, usage_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_resolve_in_both_directions() {
+        for table in PAGE_TABLES {
+            assert_eq!(page_from_name(table.name), Some(table.id), "{}", table.name);
+            for &(usage_id, name) in table.usages {
+                let usage = Usage::new(table.id, usage_id);
+                assert_eq!(usage_from_name(table.id, name), Some(usage), "{}: {}", table.name, name);
+                assert_eq!(usage_name(usage).as_deref(), Some(name));
+            }
+        }
+    }
+
+    #[test]
+    fn looks_up_usages_of_the_usb_org_tables() {
+        assert_eq!(page_name(page::KEYBOARD_KEYPAD), Some("Keyboard/Keypad"));
+        assert_eq!(usage_name(keyboard_keypad::new(0x04)).as_deref(), Some("Keyboard A"));
+        assert_eq!(usage_from_names("consumer", "volume increment"), Some(Usage::new(page::CONSUMER, 0xE9)));
+        assert_eq!(usage_name(Usage::new(page::SENSORS, 0x73)).as_deref(), Some("Motion: Accelerometer 3D"));
+        assert_eq!(usage_name(generic_desktop::MOUSE).as_deref(), Some("Mouse"));
+        assert_eq!(page_name(page::UNICODE), None);
+    }
+
+    #[test]
+    fn names_generated_usages() {
+        assert_eq!(usage_name(button::new(3)).as_deref(), Some("Button 3"));
+        assert_eq!(usage_from_names("Ordinal", "instance 2"), Some(Usage::new(page::ORDINAL, 2)));
+        assert_eq!(usage_from_names("Monitor Enumerated", "Enum 5"), Some(Usage::new(page::MONITOR_ENUMERATED, 5)));
+        assert_eq!(usage_from_names("Button", "Button 0"), None);
+    }
+}
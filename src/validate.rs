@@ -0,0 +1,956 @@
+//! Validation of collections against the rules of the USB HID specification and of common hosts.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use super::collection::{Collection, CollectionItem};
+use super::descriptor::ReportDescriptor;
+use super::error::ReportIdError;
+use super::field_types::{CollectionType, LogicalValue, ReportId};
+use super::format::{MAX_FIELD_SIZE, MAX_REPORT_SIZE};
+use super::iter::ReportIdCheck;
+use super::report::{Report, ReportType};
+use super::unit::Unit;
+use super::usage::Usage;
+use super::usage_tables::{generic_desktop, keyboard_keypad, page};
+
+/// Location of an item in the collection tree, as the index of the item at each level.
+///
+/// The first index selects a top-level collection, and each following index selects an item of
+/// the collection before it. A path is written as the indices separated by '/', such as "/0/2/1".
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ItemPath(Vec<usize>);
+
+impl ItemPath {
+    /// Construct the path of a top-level collection.
+    pub fn top_level(index: usize) -> Self {
+        Self(vec![index])
+    }
+
+    /// Returns the path of the item at the given index within the item at this path.
+    pub fn child(&self, index: usize) -> Self {
+        let mut indices = self.0.clone();
+        indices.push(index);
+        Self(indices)
+    }
+
+    /// Returns the index of the item at each level.
+    pub fn indices(&self) -> &[usize] {
+        &self.0
+    }
+
+    /// Returns the number of collections containing the item at this path. Top-level collections
+    /// have a depth of 0.
+    pub fn depth(&self) -> usize {
+        self.0.len().saturating_sub(1)
+    }
+}
+
+impl Display for ItemPath {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return "/".fmt(fmt);
+        }
+        for index in self.0.iter() {
+            write!(fmt, "/{}", index)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Whether a diagnostic makes a descriptor invalid, or only suspicious.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => "warning".fmt(fmt),
+            Self::Error => "error".fmt(fmt),
+        }
+    }
+}
+
+/// A host whose parser has rules beyond the USB HID specification.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HostProfile {
+    /// The limits of hid-core in the Linux kernel, which Android also uses to parse descriptors.
+    Linux,
+    /// The rules of the Windows HID parser.
+    Windows,
+    /// The rules of the IOHIDFamily parser of macOS.
+    MacOs,
+    /// The fixed input report of a keyboard in the boot protocol, described in Appendix B.1 of the
+    /// USB HID specification.
+    BootKeyboard,
+    /// The fixed input report of a mouse in the boot protocol, described in Appendix B.2 of the
+    /// USB HID specification.
+    BootMouse,
+}
+
+impl Display for HostProfile {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linux => "Linux".fmt(fmt),
+            Self::Windows => "Windows".fmt(fmt),
+            Self::MacOs => "macOS".fmt(fmt),
+            Self::BootKeyboard => "boot keyboard".fmt(fmt),
+            Self::BootMouse => "boot mouse".fmt(fmt),
+        }
+    }
+}
+
+// Limits of the Linux hid-core parser, from include/linux/hid.h and drivers/hid/hid-core.c.
+const LINUX_MAX_USAGES: u32 = 12288;
+const LINUX_MAX_FIELDS: usize = 256;
+const LINUX_MAX_REPORT_SIZE: u32 = MAX_FIELD_SIZE;
+/// Longest report data, in bits, leaving room for the report ID in a 16384 byte buffer.
+const LINUX_MAX_REPORT_BITS: u64 = MAX_REPORT_SIZE as u64;
+
+
+/// A rule broken by an item of a collection.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiagnosticKind {
+    /// The logical minimum is greater than the logical maximum.
+    LogicalMinimumAboveMaximum {
+        minimum: LogicalValue,
+        maximum: LogicalValue,
+    },
+    /// A physical range is given without a unit.
+    PhysicalRangeWithoutUnit,
+    /// A report uses the reserved Report ID 0.
+    ReportIdZero,
+    /// Some reports have IDs while others do not. This is the rule reported by
+    /// ReportIdError::MissingId.
+    MixedReportIds,
+    /// A variable item has more usages than fields, or an array item has no usages. A variable
+    /// item may have fewer usages than fields, as the last usage applies to the remaining fields.
+    UsageCountMismatch {
+        usages: u32,
+        expected: u64,
+    },
+    /// The reports of a type and ID do not end on a byte boundary, and need padding.
+    UnalignedReport {
+        report_type: ReportType,
+        report_id: Option<ReportId>,
+        bit_size: u64,
+    },
+    /// A top-level collection is not an Application collection.
+    NonApplicationTopLevel(CollectionType),
+    /// The Volatile flag, which is reserved for Input items, is set on an Input item.
+    VolatileInput,
+    /// A report has a size of 0 bits.
+    ZeroReportSize,
+    /// A report has more usages than the host allows in a single field.
+    TooManyUsages {
+        usages: u32,
+        limit: u32,
+    },
+    /// A report size is larger than the host allows.
+    ReportSizeTooLarge {
+        report_size: u32,
+        limit: u32,
+    },
+    /// A report count is larger than the host allows.
+    ReportCountTooLarge {
+        report_count: u32,
+        limit: u32,
+    },
+    /// The reports of a type and ID have more fields than the host allows.
+    TooManyFields {
+        report_type: ReportType,
+        report_id: Option<ReportId>,
+        fields: usize,
+        limit: usize,
+    },
+    /// The reports of a type and ID are longer than the host allows.
+    ReportTooLong {
+        report_type: ReportType,
+        report_id: Option<ReportId>,
+        bit_size: u64,
+        limit: u64,
+    },
+    /// A Report ID is used by more than one top-level collection.
+    ReportIdSharedAcrossCollections(ReportId),
+    /// There are several top-level collections, but reports without an ID.
+    MissingIdWithMultipleCollections,
+    /// A report has a report count of 0, and no fields.
+    ZeroReportCount,
+    /// The reports do not match the fixed layout of the boot protocol.
+    BootLayoutMismatch(&'static str),
+}
+
+impl DiagnosticKind {
+    /// Returns the severity of breaking this rule.
+    pub const fn severity(&self) -> Severity {
+        match self {
+            Self::LogicalMinimumAboveMaximum { .. }
+            | Self::ReportIdZero
+            | Self::MixedReportIds
+            | Self::NonApplicationTopLevel(..)
+            | Self::ZeroReportSize => Severity::Error,
+            Self::PhysicalRangeWithoutUnit
+            | Self::UsageCountMismatch { .. }
+            | Self::UnalignedReport { .. }
+            | Self::VolatileInput => Severity::Warning,
+            Self::TooManyUsages { .. }
+            | Self::ReportSizeTooLarge { .. }
+            | Self::ReportCountTooLarge { .. }
+            | Self::TooManyFields { .. }
+            | Self::ReportTooLong { .. }
+            | Self::ReportIdSharedAcrossCollections(..)
+            | Self::MissingIdWithMultipleCollections
+            | Self::ZeroReportCount
+            | Self::BootLayoutMismatch(..) => Severity::Error,
+        }
+    }
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LogicalMinimumAboveMaximum { minimum, maximum } =>
+                write!(fmt, "logical minimum {} is above logical maximum {}", minimum, maximum),
+            Self::PhysicalRangeWithoutUnit => "physical range without a unit".fmt(fmt),
+            Self::ReportIdZero => "report ID 0 is reserved".fmt(fmt),
+            Self::MixedReportIds => "reports should have IDs if one has an ID".fmt(fmt),
+            Self::UsageCountMismatch { usages, expected } =>
+                write!(fmt, "{} usages for {} fields", usages, expected),
+            Self::UnalignedReport { report_type, report_id, bit_size } => {
+                write!(fmt, "{:?} report", report_type)?;
+                if let Some(report_id) = report_id {
+                    write!(fmt, " {}", report_id)?;
+                }
+                write!(fmt, " of {} bits is not byte aligned", bit_size)
+            },
+            Self::NonApplicationTopLevel(collection_type) =>
+                write!(fmt, "top-level collection is {:?}, not Application", collection_type),
+            Self::VolatileInput => "volatile flag set on an input".fmt(fmt),
+            Self::ZeroReportSize => "report size of 0 bits".fmt(fmt),
+            Self::TooManyUsages { usages, limit } =>
+                write!(fmt, "{} usages, more than the limit of {}", usages, limit),
+            Self::ReportSizeTooLarge { report_size, limit } =>
+                write!(fmt, "report size of {} bits, more than the limit of {}", report_size, limit),
+            Self::ReportCountTooLarge { report_count, limit } =>
+                write!(fmt, "report count of {}, more than the limit of {}", report_count, limit),
+            Self::TooManyFields { report_type, report_id, fields, limit } => {
+                write!(fmt, "{:?} report", report_type)?;
+                if let Some(report_id) = report_id {
+                    write!(fmt, " {}", report_id)?;
+                }
+                write!(fmt, " has {} fields, more than the limit of {}", fields, limit)
+            },
+            Self::ReportTooLong { report_type, report_id, bit_size, limit } => {
+                write!(fmt, "{:?} report", report_type)?;
+                if let Some(report_id) = report_id {
+                    write!(fmt, " {}", report_id)?;
+                }
+                write!(fmt, " of {} bits is longer than the limit of {}", bit_size, limit)
+            },
+            Self::ReportIdSharedAcrossCollections(report_id) =>
+                write!(fmt, "report ID {} is used by several top-level collections", report_id),
+            Self::MissingIdWithMultipleCollections =>
+                "reports need IDs when there are several top-level collections".fmt(fmt),
+            Self::ZeroReportCount => "report count of 0".fmt(fmt),
+            Self::BootLayoutMismatch(reason) => write!(fmt, "not a boot report: {}", reason),
+        }
+    }
+}
+
+/// A rule broken by the item at a path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub path: ItemPath,
+    pub kind: DiagnosticKind,
+    /// The host which has this rule, or None for rules of the USB HID specification.
+    pub host: Option<HostProfile>,
+}
+
+impl Diagnostic {
+    pub fn new(path: ItemPath, kind: DiagnosticKind) -> Self {
+        Self { path, kind, host: None }
+    }
+
+    /// Construct a diagnostic for a rule of the given host.
+    pub fn for_host(host: HostProfile, path: ItemPath, kind: DiagnosticKind) -> Self {
+        Self { path, kind, host: Some(host) }
+    }
+
+    /// Returns the severity of this diagnostic. A host rejects any descriptor which breaks one of
+    /// its rules, so host diagnostics are always errors.
+    pub const fn severity(&self) -> Severity {
+        match self.host {
+            Some(..) => Severity::Error,
+            None => self.kind.severity(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} at {}", self.severity(), self.path)?;
+        if let Some(host) = self.host {
+            write!(fmt, " ({})", host)?;
+        }
+        write!(fmt, ": {}", self.kind)
+    }
+}
+
+
+/// Collect every report with its path, in descriptor order.
+fn collect_reports<'a>(collection: &'a Collection, path: &ItemPath, reports: &mut Vec<(ItemPath, &'a Report)>) {
+    for (index, item) in collection.items().iter().enumerate() {
+        match item {
+            CollectionItem::Report(report) => reports.push((path.child(index), report)),
+            CollectionItem::Collection(collection) =>
+                collect_reports(collection, &path.child(index), reports),
+            CollectionItem::LongItem(_) => (),
+        }
+    }
+}
+
+/// Check the rules which apply to a single report.
+fn validate_report(path: &ItemPath, report: &Report, diagnostics: &mut Vec<Diagnostic>) {
+    let mut push = |kind| diagnostics.push(Diagnostic::new(path.clone(), kind));
+    let report_flags = report.main.report_flags;
+
+    if report.report_size == 0 {
+        push(DiagnosticKind::ZeroReportSize);
+    }
+    if report.report_id == Some(0) {
+        push(DiagnosticKind::ReportIdZero);
+    }
+    if report.is_input() && report_flags.is_volatile() {
+        push(DiagnosticKind::VolatileInput);
+    }
+    if report_flags.is_constant() {
+        // Padding has no values or usages.
+        return;
+    }
+
+    if report.logical_minimum > report.logical_maximum {
+        push(DiagnosticKind::LogicalMinimumAboveMaximum {
+            minimum: report.logical_minimum,
+            maximum: report.logical_maximum,
+        });
+    }
+    let has_physical_range = report.physical_minimum.unwrap_or(0) != 0
+        || report.physical_maximum.unwrap_or(0) != 0;
+    if has_physical_range && report.unit.unwrap_or(Unit::NONE) == Unit::NONE {
+        push(DiagnosticKind::PhysicalRangeWithoutUnit);
+    }
+
+    let usages = report.usage_set.len();
+    let expected = if report_flags.is_buffered_bytes() {
+        // Buffered bytes are a single field.
+        1
+    } else {
+        report.report_count.into()
+    };
+    let mismatch = if report_flags.is_array() {
+        usages == 0
+    } else {
+        u64::from(usages) > expected
+    };
+    if mismatch {
+        push(DiagnosticKind::UsageCountMismatch { usages, expected });
+    }
+}
+
+/// The reports which share a type and ID.
+struct ReportGroup<'a> {
+    report_type: ReportType,
+    report_id: Option<ReportId>,
+    /// Total size of the reports, in bits.
+    bit_size: u64,
+    reports: Vec<&'a (ItemPath, &'a Report)>,
+}
+
+impl ReportGroup<'_> {
+    /// Returns the path of the last report of this group.
+    fn last_path(&self) -> ItemPath {
+        self.reports[self.reports.len() - 1].0.clone()
+    }
+}
+
+/// Group reports by type and ID, in order of type and ID.
+fn report_groups<'a>(reports: &'a [(ItemPath, &'a Report)]) -> Vec<ReportGroup<'a>> {
+    let mut groups: BTreeMap<(ReportType, Option<ReportId>), ReportGroup> = BTreeMap::new();
+    for entry in reports.iter() {
+        let report = entry.1;
+        let report_type = report.report_type();
+        let group = groups.entry((report_type, report.report_id))
+            .or_insert(ReportGroup {
+                report_type,
+                report_id: report.report_id,
+                bit_size: 0,
+                reports: Vec::new(),
+            });
+        group.bit_size += u64::from(report.report_size) * u64::from(report.report_count);
+        group.reports.push(entry);
+    }
+    groups.into_values().collect()
+}
+
+/// Check the limits of the Linux hid-core parser.
+fn validate_linux(host: HostProfile, reports: &[(ItemPath, &Report)], diagnostics: &mut Vec<Diagnostic>) {
+    for (path, report) in reports.iter() {
+        let mut push = |kind| diagnostics.push(Diagnostic::for_host(host, path.clone(), kind));
+        if report.report_size > LINUX_MAX_REPORT_SIZE {
+            push(DiagnosticKind::ReportSizeTooLarge {
+                report_size: report.report_size,
+                limit: LINUX_MAX_REPORT_SIZE,
+            });
+        }
+        if report.report_count > LINUX_MAX_USAGES {
+            push(DiagnosticKind::ReportCountTooLarge {
+                report_count: report.report_count,
+                limit: LINUX_MAX_USAGES,
+            });
+        }
+        if report.usage_set.len() > LINUX_MAX_USAGES {
+            push(DiagnosticKind::TooManyUsages {
+                usages: report.usage_set.len(),
+                limit: LINUX_MAX_USAGES,
+            });
+        }
+    }
+
+    for group in report_groups(reports) {
+        // Padding without usages does not create a field.
+        let fields = group.reports.iter()
+            .filter(|(_, report)| !report.usage_set.is_empty())
+            .count();
+        if fields > LINUX_MAX_FIELDS {
+            diagnostics.push(Diagnostic::for_host(host, group.last_path(), DiagnosticKind::TooManyFields {
+                report_type: group.report_type,
+                report_id: group.report_id,
+                fields,
+                limit: LINUX_MAX_FIELDS,
+            }));
+        }
+        if group.bit_size > LINUX_MAX_REPORT_BITS {
+            diagnostics.push(Diagnostic::for_host(host, group.last_path(), DiagnosticKind::ReportTooLong {
+                report_type: group.report_type,
+                report_id: group.report_id,
+                bit_size: group.bit_size,
+                limit: LINUX_MAX_REPORT_BITS,
+            }));
+        }
+    }
+}
+
+/// Check the rules of the Windows HID parser, which splits a descriptor into its top-level
+/// collections.
+fn validate_windows(collections: &[Collection], diagnostics: &mut Vec<Diagnostic>) {
+    let host = HostProfile::Windows;
+    let mut collection_ids: BTreeMap<ReportId, usize> = BTreeMap::new();
+    for (index, collection) in collections.iter().enumerate() {
+        let top_level = ItemPath::top_level(index);
+        let mut reports = Vec::new();
+        collect_reports(collection, &top_level, &mut reports);
+
+        // Input reports must be byte aligned within each top-level collection.
+        for group in report_groups(&reports) {
+            if group.report_type == ReportType::Input && group.bit_size % 8 != 0 {
+                diagnostics.push(Diagnostic::for_host(host, group.last_path(), DiagnosticKind::UnalignedReport {
+                    report_type: group.report_type,
+                    report_id: group.report_id,
+                    bit_size: group.bit_size,
+                }));
+            }
+        }
+
+        for (path, report) in reports.iter() {
+            if let Some(report_id) = report.report_id {
+                let owner = *collection_ids.entry(report_id).or_insert(index);
+                if owner != index {
+                    diagnostics.push(Diagnostic::for_host(
+                        host,
+                        path.clone(),
+                        DiagnosticKind::ReportIdSharedAcrossCollections(report_id),
+                    ));
+                }
+            }
+        }
+        // Point at the first report without an ID in each collection.
+        if collections.len() > 1 {
+            if let Some((path, _)) = reports.iter().find(|(_, report)| report.report_id.is_none()) {
+                diagnostics.push(Diagnostic::for_host(
+                    host,
+                    path.clone(),
+                    DiagnosticKind::MissingIdWithMultipleCollections,
+                ));
+            }
+        }
+    }
+}
+
+/// Check the rules of the IOHIDFamily parser of macOS, which rejects main items without fields, and
+/// routes reports to the elements of every top-level collection by report ID.
+fn validate_macos(collections: &[Collection], reports: &[(ItemPath, &Report)], diagnostics: &mut Vec<Diagnostic>) {
+    let host = HostProfile::MacOs;
+    for (path, report) in reports.iter() {
+        if report.report_count == 0 {
+            diagnostics.push(Diagnostic::for_host(host, path.clone(), DiagnosticKind::ZeroReportCount));
+        }
+    }
+    // Point at the first report without an ID in the descriptor.
+    if collections.len() > 1 {
+        if let Some((path, _)) = reports.iter().find(|(_, report)| report.report_id.is_none()) {
+            diagnostics.push(Diagnostic::for_host(
+                host,
+                path.clone(),
+                DiagnosticKind::MissingIdWithMultipleCollections,
+            ));
+        }
+    }
+}
+
+/// A single field of an input report, with its offset in bits.
+struct BootField<'a> {
+    path: &'a ItemPath,
+    report: &'a Report,
+    bit_offset: u64,
+    usage: Option<Usage>,
+}
+
+/// Expand input reports into fields, assigning usages to variable fields.
+fn boot_fields<'a>(reports: &'a [(ItemPath, &'a Report)]) -> Vec<BootField<'a>> {
+    let mut fields = Vec::new();
+    let mut bit_offset = 0;
+    for (path, report) in reports.iter().filter(|(_, report)| report.is_input()) {
+        let mut usages = report.usage_set.usages();
+        let mut usage = None;
+        for _ in 0..report.report_count {
+            if report.main.report_flags.is_variable() {
+                usage = usages.next().or(usage);
+            }
+            fields.push(BootField { path, report, bit_offset, usage });
+            bit_offset += u64::from(report.report_size);
+        }
+    }
+    fields
+}
+
+/// Check that input reports match the layout of a boot keyboard or boot mouse.
+fn validate_boot(host: HostProfile, reports: &[(ItemPath, &Report)], diagnostics: &mut Vec<Diagnostic>) {
+    let top_level = ItemPath::top_level(0);
+    let mut push = |path: &ItemPath, reason| {
+        diagnostics.push(Diagnostic::for_host(host, path.clone(), DiagnosticKind::BootLayoutMismatch(reason)))
+    };
+
+    if let Some((path, _)) = reports.iter().find(|(_, report)| report.report_id.is_some()) {
+        push(path, "boot reports have no report ID");
+    }
+    let fields = boot_fields(reports);
+    let field_at = |bit_offset: u64| fields.iter().find(|field| field.bit_offset == bit_offset);
+    let input_size: u64 = fields.iter().map(|field| u64::from(field.report.report_size)).sum();
+
+    match host {
+        HostProfile::BootKeyboard => {
+            if input_size != 64 {
+                push(&top_level, "boot keyboard input report is not 8 bytes");
+            }
+            // Modifier byte: 8 variable bits, Left Control to Right GUI.
+            for (index, bit_offset) in (0..8).enumerate() {
+                let is_modifier = field_at(bit_offset).is_some_and(|field| {
+                    field.report.report_size == 1
+                        && field.report.main.report_flags.is_variable()
+                        && field.usage == Some(keyboard_keypad::new(0xE0 + index as u16))
+                });
+                if !is_modifier {
+                    push(field_at(bit_offset).map_or(&top_level, |field| field.path),
+                         "byte 0 should hold the 8 modifier keys");
+                    break;
+                }
+            }
+            // Reserved byte.
+            if !field_at(8).is_some_and(|field| field.report.main.report_flags.is_constant()) {
+                push(field_at(8).map_or(&top_level, |field| field.path), "byte 1 should be reserved");
+            }
+            // Key array: 6 bytes of keyboard usages.
+            let is_key_array = field_at(16).is_some_and(|field| {
+                let report = field.report;
+                report.main.report_flags.is_array()
+                    && report.report_size == 8
+                    && report.report_count == 6
+                    && report.usage_set.usages().all(|usage| usage.page() == page::KEYBOARD_KEYPAD)
+            });
+            if !is_key_array {
+                push(field_at(16).map_or(&top_level, |field| field.path),
+                     "bytes 2 to 7 should be an array of 6 keys");
+            }
+        },
+        HostProfile::BootMouse => {
+            if input_size < 24 {
+                push(&top_level, "boot mouse input report is shorter than 3 bytes");
+            }
+            // Buttons: at least 3 variable bits of the Button page, in byte 0.
+            let button_count = (0..8)
+                .take_while(|&bit_offset| field_at(bit_offset).is_some_and(|field| {
+                    field.report.report_size == 1
+                        && field.report.main.report_flags.is_variable()
+                        && field.usage.is_some_and(|usage| usage.page() == page::BUTTON)
+                }))
+                .count();
+            if button_count < 3 {
+                push(field_at(0).map_or(&top_level, |field| field.path),
+                     "byte 0 should start with at least 3 buttons");
+            }
+            // X and Y: relative, 8-bit values in bytes 1 and 2.
+            for (bit_offset, usage, reason) in [
+                (8, generic_desktop::X, "byte 1 should be a relative 8-bit X"),
+                (16, generic_desktop::Y, "byte 2 should be a relative 8-bit Y"),
+            ] {
+                let is_axis = field_at(bit_offset).is_some_and(|field| {
+                    field.report.report_size == 8
+                        && field.report.main.report_flags.is_relative()
+                        && field.usage == Some(usage)
+                });
+                if !is_axis {
+                    push(field_at(bit_offset).map_or(&top_level, |field| field.path), reason);
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Check a set of top-level collections, returning a diagnostic for each broken rule.
+///
+/// Diagnostics are returned in descriptor order, with the rules which apply to the whole
+/// descriptor after the rules which apply to single items.
+pub fn validate(collections: &[Collection]) -> Box<[Diagnostic]> {
+    let mut diagnostics = Vec::new();
+    let mut reports = Vec::new();
+    for (index, collection) in collections.iter().enumerate() {
+        let path = ItemPath::top_level(index);
+        if collection.collection_type != CollectionType::Application {
+            diagnostics.push(Diagnostic::new(
+                path.clone(),
+                DiagnosticKind::NonApplicationTopLevel(collection.collection_type),
+            ));
+        }
+        collect_reports(collection, &path, &mut reports);
+    }
+
+    for (path, report) in reports.iter() {
+        validate_report(path, report, &mut diagnostics);
+    }
+
+    // Either every report has an ID or no report has one. Point at the first report which differs
+    // from the reports before it, as ToReportIterator::report_ids does. Report ID 0 is reported
+    // for each report above.
+    let mut id_check = ReportIdCheck::default();
+    let mixed_id = reports.iter()
+        .find(|(_, report)| matches!(id_check.check(report), Err(ReportIdError::MissingId { .. })));
+    if let Some((path, _)) = mixed_id {
+        diagnostics.push(Diagnostic::new(path.clone(), DiagnosticKind::MixedReportIds));
+    }
+
+    // Each report, by type and ID, should end on a byte boundary. Point at the last report of
+    // each.
+    for group in report_groups(&reports) {
+        if group.bit_size % 8 != 0 {
+            diagnostics.push(Diagnostic::new(group.last_path(), DiagnosticKind::UnalignedReport {
+                report_type: group.report_type,
+                report_id: group.report_id,
+                bit_size: group.bit_size,
+            }));
+        }
+    }
+
+    diagnostics.into_boxed_slice()
+}
+
+/// Check a set of top-level collections against the USB HID specification and the rules of each
+/// given host. Host diagnostics follow the diagnostics of the specification, in the order of the
+/// hosts.
+///
+/// The boot profiles check the first top-level collection, as a boot device has a single input
+/// report.
+pub fn validate_for_hosts(collections: &[Collection], hosts: &[HostProfile]) -> Box<[Diagnostic]> {
+    let mut diagnostics = Vec::from(validate(collections));
+    let mut reports = Vec::new();
+    for (index, collection) in collections.iter().enumerate() {
+        collect_reports(collection, &ItemPath::top_level(index), &mut reports);
+    }
+
+    for &host in hosts {
+        match host {
+            HostProfile::Linux =>
+                validate_linux(host, &reports, &mut diagnostics),
+            HostProfile::Windows => validate_windows(collections, &mut diagnostics),
+            HostProfile::MacOs => validate_macos(collections, &reports, &mut diagnostics),
+            HostProfile::BootKeyboard | HostProfile::BootMouse => {
+                let first_reports: Vec<_> = reports.iter()
+                    .filter(|(path, _)| path.indices().first() == Some(&0))
+                    .cloned()
+                    .collect();
+                validate_boot(host, &first_reports, &mut diagnostics);
+            },
+        }
+    }
+    diagnostics.into_boxed_slice()
+}
+
+impl Collection {
+    /// Check this collection as the only top-level collection of a descriptor. Paths start with
+    /// index 0 for this collection.
+    pub fn validate(&self) -> Box<[Diagnostic]> {
+        validate(std::slice::from_ref(self))
+    }
+
+    /// Check this collection as the only top-level collection of a descriptor, for each given
+    /// host.
+    pub fn validate_for_hosts(&self, hosts: &[HostProfile]) -> Box<[Diagnostic]> {
+        validate_for_hosts(std::slice::from_ref(self), hosts)
+    }
+}
+
+impl ReportDescriptor {
+    /// Check every top-level collection of this descriptor. Paths start with the index of the
+    /// top-level collection.
+    pub fn validate(&self) -> Box<[Diagnostic]> {
+        validate(self.collections())
+    }
+
+    /// Check every top-level collection of this descriptor, for each given host.
+    pub fn validate_for_hosts(&self, hosts: &[HostProfile]) -> Box<[Diagnostic]> {
+        validate_for_hosts(self.collections(), hosts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::field_types::ReportFlags;
+    use super::super::iter::ToReportIterator;
+    use super::super::decompiler::Decompiler;
+    use super::super::report::ReportMain;
+    use super::super::usage::UsageSet;
+    use std::str::FromStr;
+
+    fn variable(ids: &[u16], report_count: u32) -> Report {
+        let usage_set = ids.iter()
+            .fold(UsageSet::empty(), |usage_set, &id| usage_set.with_usage(Usage::new(0x01, id)));
+        Report::new_input(ReportFlags::new().as_variable(), usage_set, -127, 127, 8, report_count)
+    }
+
+    fn application<I: Into<CollectionItem>>(items: Vec<I>) -> Collection {
+        let items: Box<[CollectionItem]> = items.into_iter().map(Into::into).collect();
+        Collection::new(CollectionType::Application, generic_desktop::MOUSE, items)
+    }
+
+    fn kinds(collection: &Collection) -> Vec<DiagnosticKind> {
+        collection.validate().iter().map(|diagnostic| diagnostic.kind.clone()).collect()
+    }
+
+    #[test]
+    fn accepts_a_repeated_last_usage() {
+        // Three fields share the usage of the last one.
+        assert_eq!(kinds(&application(vec![variable(&[0x30], 3)])), []);
+    }
+
+    #[test]
+    fn reports_each_rule_at_its_item() {
+        let mut inverted = variable(&[0x30], 1);
+        (inverted.logical_minimum, inverted.logical_maximum) = (10, -10);
+        let mut physical = variable(&[0x30], 1);
+        (physical.physical_minimum, physical.physical_maximum) = (Some(0), Some(100));
+        let mut volatile = variable(&[0x30], 1);
+        volatile.main.report_flags = ReportFlags::new().as_variable().as_volatile();
+        let mut empty = variable(&[0x30], 1);
+        empty.report_size = 0;
+        let mut unaligned = variable(&[0x30], 1);
+        unaligned.report_size = 3;
+
+        let cases = [
+            (inverted, DiagnosticKind::LogicalMinimumAboveMaximum { minimum: 10, maximum: -10 }),
+            (physical, DiagnosticKind::PhysicalRangeWithoutUnit),
+            (variable(&[0x30], 1).with_report_id(0), DiagnosticKind::ReportIdZero),
+            (volatile, DiagnosticKind::VolatileInput),
+            (empty, DiagnosticKind::ZeroReportSize),
+            (unaligned, DiagnosticKind::UnalignedReport { report_type: ReportType::Input, report_id: None, bit_size: 11 }),
+        ];
+        for (report, kind) in cases {
+            // The report follows a valid report, at index 1 of the top-level collection.
+            let collection = application(vec![variable(&[0x31], 1), report]);
+            assert_eq!(&*collection.validate(), &[Diagnostic::new(ItemPath(vec![0, 1]), kind)]);
+        }
+
+        let physical = Collection::new(CollectionType::Physical, generic_desktop::POINTER, (variable(&[0x30], 1),));
+        assert_eq!(&*physical.validate(), &[Diagnostic::new(
+            ItemPath(vec![0]),
+            DiagnosticKind::NonApplicationTopLevel(CollectionType::Physical),
+        )]);
+    }
+
+    #[test]
+    fn accepts_valid_reports() {
+        let mut physical = variable(&[0x30], 1);
+        (physical.physical_minimum, physical.physical_maximum) = (Some(0), Some(100));
+        physical.unit = Some(Unit::from_str("cm").unwrap());
+        let mut volatile_feature = variable(&[0x31], 1);
+        volatile_feature.main = ReportMain::new_feature(ReportFlags::new().as_variable().as_volatile());
+        let padding = Report::new_input(ReportFlags::new().as_constant(), UsageSet::empty(), 0, 0, 1, 8);
+        let collection = application(vec![physical, volatile_feature, padding]);
+        assert_eq!(&*collection.validate(), &[]);
+    }
+
+    #[test]
+    fn reports_usage_counts_which_cannot_match() {
+        let too_many = variable(&[0x30, 0x31, 0x38], 2);
+        assert_eq!(kinds(&application(vec![too_many])),
+                   [DiagnosticKind::UsageCountMismatch { usages: 3, expected: 2 }]);
+
+        let mut no_usages = variable(&[], 6);
+        no_usages.main.report_flags = ReportFlags::new().as_array();
+        assert_eq!(kinds(&application(vec![no_usages])),
+                   [DiagnosticKind::UsageCountMismatch { usages: 0, expected: 6 }]);
+
+        // An array may have fewer usages than its logical range.
+        let mut keys = variable(&[0x30, 0x31], 1);
+        keys.main.report_flags = ReportFlags::new().as_array();
+        assert_eq!(kinds(&application(vec![keys])), []);
+    }
+
+    #[test]
+    fn reports_mixed_report_ids_like_report_ids() {
+        let collection = application(vec![
+            variable(&[0x30], 1).with_report_id(1),
+            variable(&[0x31], 1).with_report_id(2),
+            variable(&[0x38], 1),
+            variable(&[0x30], 1),
+        ]);
+        let diagnostics = collection.validate();
+        assert_eq!(&*diagnostics, &[Diagnostic::new(ItemPath(vec![0, 2]), DiagnosticKind::MixedReportIds)]);
+        assert_eq!(collection.report_ids(), Err(ReportIdError::MissingId {
+            report_type: ReportType::Input,
+            report_id: 1,
+        }));
+
+        let consistent = application(vec![variable(&[0x30], 1), variable(&[0x31], 1)]);
+        assert_eq!(kinds(&consistent), []);
+        assert_eq!(consistent.report_ids(), Ok(Box::from([])));
+    }
+
+    #[test]
+    fn reports_host_limits() {
+        let mut wide = variable(&[0x30], 1);
+        wide.report_size = 512;
+        let collection = application(vec![wide]);
+        let diagnostics = collection.validate_for_hosts(&[HostProfile::Linux]);
+        assert_eq!(&*diagnostics, &[Diagnostic::for_host(
+            HostProfile::Linux,
+            ItemPath(vec![0, 0]),
+            DiagnosticKind::ReportSizeTooLarge { report_size: 512, limit: 256 },
+        )]);
+        assert_eq!(diagnostics[0].severity(), Severity::Error);
+        assert_eq!(diagnostics[0].to_string(),
+                   "error at /0/0 (Linux): report size of 512 bits, more than the limit of 256");
+    }
+
+    // Appendix E.6 of the USB HID specification, with the LED output report.
+    const BOOT_KEYBOARD: [u8; 63] = [
+        0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0x05, 0x07, 0x19, 0xE0, 0x29, 0xE7, 0x15, 0x00, 0x25, 0x01,
+        0x75, 0x01, 0x95, 0x08, 0x81, 0x02, 0x95, 0x01, 0x75, 0x08, 0x81, 0x01, 0x95, 0x05, 0x75, 0x01,
+        0x05, 0x08, 0x19, 0x01, 0x29, 0x05, 0x91, 0x02, 0x95, 0x01, 0x75, 0x03, 0x91, 0x01, 0x95, 0x06,
+        0x75, 0x08, 0x15, 0x00, 0x25, 0x65, 0x05, 0x07, 0x19, 0x00, 0x29, 0x65, 0x81, 0x00, 0xC0,
+    ];
+
+    // Appendix E.10 of the USB HID specification.
+    const BOOT_MOUSE: [u8; 50] = [
+        0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x09, 0x01, 0xA1, 0x00, 0x05, 0x09, 0x19, 0x01, 0x29, 0x03,
+        0x15, 0x00, 0x25, 0x01, 0x95, 0x03, 0x75, 0x01, 0x81, 0x02, 0x95, 0x01, 0x75, 0x05, 0x81, 0x01,
+        0x05, 0x01, 0x09, 0x30, 0x09, 0x31, 0x15, 0x81, 0x25, 0x7F, 0x75, 0x08, 0x95, 0x02, 0x81, 0x06,
+        0xC0, 0xC0,
+    ];
+
+    fn decompile(bytes: &[u8]) -> ReportDescriptor {
+        Decompiler::from_bytes(bytes).unwrap().descriptor().unwrap()
+    }
+
+    #[test]
+    fn accepts_the_boot_descriptors() {
+        let every_host = [HostProfile::Linux, HostProfile::Windows, HostProfile::MacOs];
+        let keyboard = decompile(&BOOT_KEYBOARD);
+        assert_eq!(&*keyboard.validate_for_hosts(&every_host), &[]);
+        assert_eq!(&*keyboard.validate_for_hosts(&[HostProfile::BootKeyboard]), &[]);
+        let mouse = decompile(&BOOT_MOUSE);
+        assert_eq!(&*mouse.validate_for_hosts(&every_host), &[]);
+        assert_eq!(&*mouse.validate_for_hosts(&[HostProfile::BootMouse]), &[]);
+    }
+
+    #[test]
+    fn rejects_other_layouts_for_boot_profiles() {
+        let mouse = decompile(&BOOT_MOUSE);
+        let diagnostics = mouse.validate_for_hosts(&[HostProfile::BootKeyboard]);
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.host == Some(HostProfile::BootKeyboard)));
+        assert_eq!(diagnostics[0], Diagnostic::for_host(
+            HostProfile::BootKeyboard,
+            ItemPath::top_level(0),
+            DiagnosticKind::BootLayoutMismatch("boot keyboard input report is not 8 bytes"),
+        ));
+
+        // X and Y of 16 bits each.
+        let mut wide_axes = BOOT_MOUSE;
+        wide_axes[43] = 0x10;
+        let diagnostics = decompile(&wide_axes).validate_for_hosts(&[HostProfile::BootMouse]);
+        assert_eq!(&*diagnostics, &[
+            Diagnostic::for_host(HostProfile::BootMouse, ItemPath(vec![0, 0, 2]),
+                                 DiagnosticKind::BootLayoutMismatch("byte 1 should be a relative 8-bit X")),
+            Diagnostic::for_host(HostProfile::BootMouse, ItemPath::top_level(0),
+                                 DiagnosticKind::BootLayoutMismatch("byte 2 should be a relative 8-bit Y")),
+        ]);
+
+        // Report ID (1) after Usage (Keyboard).
+        let with_id = [&BOOT_KEYBOARD[..4], &[0x85, 0x01], &BOOT_KEYBOARD[4..]].concat();
+        let diagnostics = decompile(&with_id).validate_for_hosts(&[HostProfile::BootKeyboard]);
+        assert_eq!(&*diagnostics, &[Diagnostic::for_host(HostProfile::BootKeyboard, ItemPath(vec![0, 0]),
+            DiagnosticKind::BootLayoutMismatch("boot reports have no report ID"))]);
+    }
+
+    #[test]
+    fn reports_windows_rules_for_each_top_level_collection() {
+        let mut three_bits = variable(&[0x30], 1).with_report_id(1);
+        three_bits.report_size = 3;
+        let mut five_bits = variable(&[0x31], 1).with_report_id(1);
+        five_bits.report_size = 5;
+        // Together, the reports with ID 1 are aligned, as the specification requires.
+        let collections = [application(vec![three_bits]), application(vec![five_bits])];
+        assert_eq!(&*validate(&collections), &[]);
+
+        let unaligned = |path, bit_size| Diagnostic::for_host(HostProfile::Windows, ItemPath(path),
+            DiagnosticKind::UnalignedReport { report_type: ReportType::Input, report_id: Some(1), bit_size });
+        assert_eq!(&*validate_for_hosts(&collections, &[HostProfile::Windows]), &[
+            unaligned(vec![0, 0], 3),
+            unaligned(vec![1, 0], 5),
+            Diagnostic::for_host(HostProfile::Windows, ItemPath(vec![1, 0]),
+                                 DiagnosticKind::ReportIdSharedAcrossCollections(1)),
+        ]);
+    }
+
+    #[test]
+    fn reports_macos_rules() {
+        let empty = variable(&[], 0);
+        let collections = [application(vec![variable(&[0x30], 1)]), application(vec![empty])];
+        let diagnostics = validate_for_hosts(&collections, &[HostProfile::MacOs]);
+        assert_eq!(&*diagnostics, &[
+            Diagnostic::for_host(HostProfile::MacOs, ItemPath(vec![1, 0]), DiagnosticKind::ZeroReportCount),
+            Diagnostic::for_host(HostProfile::MacOs, ItemPath(vec![0, 0]),
+                                 DiagnosticKind::MissingIdWithMultipleCollections),
+        ]);
+
+        let with_ids = [
+            application(vec![variable(&[0x30], 1).with_report_id(1)]),
+            application(vec![variable(&[0x31], 1).with_report_id(2)]),
+        ];
+        assert_eq!(&*validate_for_hosts(&with_ids, &[HostProfile::MacOs]), &[]);
+    }
+}